@@ -1,6 +1,8 @@
-use reqwest::StatusCode;
+use reqwest::{StatusCode, Url};
 use serde::Deserialize;
 
+use crate::throttle::CircuitOpen;
+
 mod status_serde {
     use reqwest::StatusCode;
     use serde::{de, Deserialize, Deserializer};
@@ -24,6 +26,10 @@ pub(crate) struct ErrorResponse {
 }
 
 impl ErrorResponse {
+    pub(crate) fn is_not_found(&self) -> bool {
+        self.status == StatusCode::NOT_FOUND
+    }
+
     fn into_message(self) -> Option<String> {
         self.message.or(self.cause_en)
     }
@@ -32,13 +38,36 @@ impl ErrorResponse {
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error(transparent)]
-    Http(#[from] reqwest_middleware::Error),
+    Http(reqwest_middleware::Error),
     #[error("bad credentials")]
     BadCredentials,
     #[error("unauthenticated")]
     Unauthenticated,
     #[error("unknown api error: {0}")]
     Unknown(String),
+    #[error("refusing to fetch {0}")]
+    UntrustedUrl(Url),
+    #[error("no such area: {0}")]
+    AreaNotFound(String),
+    #[error("no such vacancy: {0}")]
+    VacancyNotFound(crate::PropertyId),
+    /// The website has changed in a way that the scraper does not understand.
+    #[error("unexpected page layout: {0}")]
+    Layout(&'static str),
+    /// AF has been failing, so the request was not sent.
+    #[error("AF is unavailable")]
+    UpstreamUnavailable,
+}
+
+impl From<reqwest_middleware::Error> for Error {
+    fn from(value: reqwest_middleware::Error) -> Self {
+        match value {
+            reqwest_middleware::Error::Middleware(ref e) if e.is::<CircuitOpen>() => {
+                Self::UpstreamUnavailable
+            }
+            _ => Self::Http(value),
+        }
+    }
 }
 
 impl From<reqwest::Error> for Error {
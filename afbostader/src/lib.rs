@@ -1,12 +1,8 @@
 use error::ErrorResponse;
-use reqwest::{IntoUrl, Url};
+use reqwest::{header::LOCATION, redirect, IntoUrl, StatusCode, Url};
 use reqwest_middleware::{ClientBuilder, ClientWithMiddleware, RequestBuilder};
 use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
 use secrecy::{ExposeSecret, SecretString};
-use select::{
-    document::Document,
-    predicate::{Class, Name, Predicate},
-};
 use serde::Deserialize;
 
 pub const USER_AGENT: &str = concat!(
@@ -18,12 +14,52 @@ pub const USER_AGENT: &str = concat!(
     ")"
 );
 
+/// Hosts operated by AF Bostäder. [`Client::download`] refuses to fetch
+/// anything else.
+pub const AF_HOSTS: &[&str] = &[
+    "www.afbostader.se",
+    "afbostader.se",
+    "diremoapi.afbostader.se",
+];
+
+fn is_af_url(url: &Url) -> bool {
+    url.scheme() == "https" && url.host_str().is_some_and(|host| AF_HOSTS.contains(&host))
+}
+
+/// Only follow redirects between the [`AF_HOSTS`], so that a URL that was
+/// checked with [`is_af_url`] can't lead anywhere else.
+fn redirect_policy() -> redirect::Policy {
+    redirect::Policy::custom(|attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else if is_af_url(attempt.url()) {
+            attempt.follow()
+        } else {
+            attempt.stop()
+        }
+    })
+}
+
+/// The same limit as reqwest's default policy.
+const MAX_REDIRECTS: usize = 10;
+
+const WWW_AFBOSTADER_SE: &str = "https://www.afbostader.se";
+
+const VACANT_PRODUCTS_URL: &str =
+    "https://diremoapi.afbostader.se/redimo/rest/vacantproducts?lang=sv_SE&type=1";
+
 mod error;
 mod model;
+mod scrape;
+mod telemetry;
+mod throttle;
+mod tls;
 
 pub use error::Error;
 pub use model::*;
 use serde_json::Value;
+pub use throttle::ThrottleConfig;
+pub use tls::{Fingerprint, ParseFingerprintError, TlsConfig};
 use tracing::warn;
 
 /// User credentials.
@@ -56,17 +92,33 @@ impl Default for Client {
 
 impl Client {
     pub fn new() -> Self {
+        Self::with_tls(TlsConfig::default())
+    }
+
+    /// A client that verifies AF's certificates according to `tls`.
+    pub fn with_tls(tls: TlsConfig) -> Self {
+        Self::with_config(tls, ThrottleConfig::default())
+    }
+
+    /// A client that verifies AF's certificates according to `tls` and
+    /// limits its requests according to `throttle`. Clones of the client
+    /// share the limits.
+    pub fn with_config(tls: TlsConfig, throttle: ThrottleConfig) -> Self {
+        // AF Bostäder serves an incomplete certificate chain
+        let (tls, fetched) = tls.build();
         let client = reqwest::Client::builder()
             .user_agent(USER_AGENT)
-            // Until AF Bostäder fixes their TLS config (or we decide to
-            // try a bit harder to verify their semi-complete certificate
-            // chain), we are forced to skip TLS verification.
-            .danger_accept_invalid_certs(true)
+            .use_preconfigured_tls(tls)
+            .redirect(redirect_policy())
             .build()
             .unwrap();
         let retry_policy = ExponentialBackoff::builder().build_with_max_retries(5);
         let client = ClientBuilder::new(client)
             .with(RetryTransientMiddleware::new_with_policy(retry_policy))
+            .with(throttle::CircuitBreaker::new(&throttle))
+            .with(throttle::RateLimit::new(&throttle))
+            .with(telemetry::TraceAttempts)
+            .with(tls::CompleteChain::new(fetched))
             .build();
 
         Self {
@@ -101,145 +153,270 @@ impl Client {
         }
     }
 
+    /// Download a resource, such as [`PropertyDetail::blueprint`], from one
+    /// of the [`AF_HOSTS`]. Any other URL results in
+    /// [`Error::UntrustedUrl`], as the URL may come from upstream data that
+    /// we do not control.
+    pub async fn download(&self, url: Url) -> Result<reqwest::Response, Error> {
+        telemetry::instrument("download", async {
+            if !is_af_url(&url) {
+                return Err(Error::UntrustedUrl(url));
+            }
+
+            let res = self.get(url).send().await?.error_for_status()?;
+
+            // a redirect that the policy didn't follow
+            if res.status().is_redirection() {
+                let location = res
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| res.url().join(v).ok())
+                    .unwrap_or_else(|| res.url().clone());
+
+                return Err(Error::UntrustedUrl(location));
+            }
+
+            Ok(res)
+        })
+        .await
+    }
+
+    /// Check that the REST API responds, without reading the response.
+    pub async fn ping(&self) -> Result<(), Error> {
+        telemetry::instrument("ping", async {
+            self.inner
+                .get(VACANT_PRODUCTS_URL)
+                .send()
+                .await?
+                .error_for_status()?;
+
+            Ok(())
+        })
+        .await
+    }
+
     /// List vacant properties. This function uses the same endpoint as
     /// the frontend at
     /// [afbostader.se/lediga-bostader](https://www.afbostader.se/lediga-bostader/).
     pub async fn list_vacancies(&self) -> Result<Vec<Property>, Error> {
-        #[derive(Debug, Deserialize)]
-        #[serde(untagged)]
-        enum Response {
-            Product { product: Vec<Product> },
-            Error(ErrorResponse),
-        }
+        telemetry::instrument("list_vacancies", async {
+            #[derive(Debug, Deserialize)]
+            #[serde(untagged)]
+            enum Response {
+                Product { product: Vec<Product> },
+                Error(ErrorResponse),
+            }
 
-        match self
-            .get("https://diremoapi.afbostader.se/redimo/rest/vacantproducts?lang=sv_SE&type=1")
-            .send()
-            .await?
-            .json::<Response>()
-            .await?
-        {
-            Response::Product { product } => Ok(product
-                .into_iter()
-                .map(|product| {
-                    let mut property: Property = product.into();
-                    if !self.has_credentials() {
-                        // there is no point in keeping the unpredictable queue
-                        // position that is reported for unauthenticated calls
-                        property.queue_position.position = None;
-                    }
-                    property
-                })
-                .collect()),
-            Response::Error(e) => Err(e.into()),
-        }
+            match self
+                .get(VACANT_PRODUCTS_URL)
+                .send()
+                .await?
+                .json::<Response>()
+                .await?
+            {
+                Response::Product { product } => {
+                    telemetry::vacancies(product.len());
+
+                    Ok(product
+                        .into_iter()
+                        .map(|product| {
+                            let mut property: Property = product.into();
+                            if !self.has_credentials() {
+                                // there is no point in keeping the unpredictable queue
+                                // position that is reported for unauthenticated calls
+                                property.queue_position.position = None;
+                            }
+                            property
+                        })
+                        .collect())
+                }
+                Response::Error(e) => Err(e.into()),
+            }
+        })
+        .await
     }
 
     pub async fn vacancy_detail(&self, id: PropertyId) -> Result<PropertyDetail, Error> {
-        #[derive(Debug, Deserialize)]
-        #[serde(untagged)]
-        #[allow(clippy::large_enum_variant)]
-        enum Response {
-            Product(ProductDetail),
-            Error(ErrorResponse),
-        }
+        telemetry::instrument("vacancy_detail", async {
+            #[derive(Debug, Deserialize)]
+            #[serde(untagged)]
+            #[allow(clippy::large_enum_variant)]
+            enum Response {
+                Product(ProductDetail),
+                Error(ErrorResponse),
+            }
 
-        match self
-            .get(format!(
-                "https://diremoapi.afbostader.se/redimo/rest/vacantproducts/{id}?lang=sv_SE"
-            ))
-            .send()
-            .await?
-            .json::<Response>()
-            .await?
-        {
-            Response::Product(product) => {
-                let mut property: PropertyDetail = product.into();
-                if !self.has_credentials() {
-                    property.property.queue_position.position = None;
+            match self
+                .get(format!(
+                    "https://diremoapi.afbostader.se/redimo/rest/vacantproducts/{id}?lang=sv_SE"
+                ))
+                .send()
+                .await?
+                .json::<Response>()
+                .await?
+            {
+                Response::Product(product) => {
+                    let mut property: PropertyDetail = product.into();
+                    if !self.has_credentials() {
+                        property.property.queue_position.position = None;
+                    }
+                    Ok(property)
                 }
-                Ok(property)
+                Response::Error(e) if e.is_not_found() => Err(Error::VacancyNotFound(id)),
+                Response::Error(e) => Err(e.into()),
             }
-            Response::Error(e) => Err(e.into()),
+        })
+        .await
+    }
+
+    /// Fetch a page from the website, or `None` if it does not exist.
+    async fn www(&self, url: Url) -> Result<Option<String>, Error> {
+        let res = self.inner.get(url).send().await?;
+
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
         }
+
+        Ok(Some(res.error_for_status()?.text().await?))
     }
 
+    /// Scrape the page describing an area.
     pub async fn area_detail(&self, area_name: &str) -> Result<AreaDetail, Error> {
-        let base = Url::parse("https://www.afbostader.se").unwrap();
-
-        let html = self
-            .inner
-            .get(
-                base.join("/lediga-bostader/bostadsomraden/")
-                    .unwrap()
-                    .join(&slug::slugify(area_name))
-                    .unwrap(),
-            )
-            .send()
-            .await?
-            .text()
-            .await?;
-        let doc = Document::from(html.as_str());
-
-        let pictures = doc
-            .find(
-                Class("slideshow")
-                    .descendant(Class("slides"))
-                    .descendant(Name("img")),
-            )
-            .filter_map(|node| {
-                let alt = node
-                    .attr("alt")
-                    .map(|s| s.trim_matches('\"'))
-                    .filter(|s| !s.is_empty())
-                    .map(ToOwned::to_owned);
+        telemetry::instrument("area_detail", async {
+            let base = Url::parse(WWW_AFBOSTADER_SE).unwrap();
+            let html = self
+                .www(scrape::area_url(&base, &scrape::area_slug(area_name)))
+                .await?
+                .ok_or_else(|| Error::AreaNotFound(area_name.to_owned()))?;
 
-                Some(Picture {
-                    alt,
-                    url: base.join(node.attr("src")?).ok()?,
-                })
-            })
-            .collect();
+            scrape::area_detail(&html, &base)
+        })
+        .await
+    }
 
-        Ok(AreaDetail { pictures })
+    /// Scrape the list of all areas.
+    pub async fn list_areas(&self) -> Result<Vec<Area>, Error> {
+        telemetry::instrument("list_areas", async {
+            let base = Url::parse(WWW_AFBOSTADER_SE).unwrap();
+            let html = self
+                .www(scrape::area_url(&base, ""))
+                .await?
+                .ok_or(Error::Layout("area list not found"))?;
+
+            scrape::areas(&html, &base)
+        })
+        .await
     }
 
     pub async fn user_info(&self) -> Result<User, Error> {
-        #[derive(Debug, Deserialize)]
-        #[serde(untagged)]
-        #[allow(clippy::large_enum_variant)]
-        enum Response {
-            UserInfo(UserInfo),
-            Error(ErrorResponse),
-            Strange(Value),
-        }
+        telemetry::instrument("user_info", async {
+            #[derive(Debug, Deserialize)]
+            #[serde(untagged)]
+            #[allow(clippy::large_enum_variant)]
+            enum Response {
+                UserInfo(UserInfo),
+                Error(ErrorResponse),
+                Strange(Value),
+            }
 
-        if self.credentials.is_none() {
-            warn!("requesting user info without credentials");
-        }
+            if self.credentials.is_none() {
+                warn!("requesting user info without credentials");
+            }
 
-        match self
-            .get("https://diremoapi.afbostader.se/redimo/rest/registerForHousing/getUserInfo")
-            .send()
-            .await?
-            .json::<Response>()
-            .await?
-        {
-            Response::UserInfo(info) => Ok(info.into()),
-            Response::Error(e) => Err(e.into()),
-            Response::Strange(v) => {
-                // the api returns a UserInfo object with all values set to
-                // null if nobody is logged in
-                if let Some(obj) = v.as_object() {
-                    if !obj.is_empty() && obj.values().all(Value::is_null) {
-                        return Err(Error::Unauthenticated);
+            match self
+                .get("https://diremoapi.afbostader.se/redimo/rest/registerForHousing/getUserInfo")
+                .send()
+                .await?
+                .json::<Response>()
+                .await?
+            {
+                Response::UserInfo(info) => Ok(info.into()),
+                Response::Error(e) => Err(e.into()),
+                Response::Strange(v) => {
+                    // the api returns a UserInfo object with all values set to
+                    // null if nobody is logged in
+                    if let Some(obj) = v.as_object() {
+                        if !obj.is_empty() && obj.values().all(Value::is_null) {
+                            return Err(Error::Unauthenticated);
+                        }
                     }
+
+                    Err(Error::Unknown(format!(
+                        "unexpected json from server: {v:#?}"
+                    )))
                 }
+            }
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{StatusCode, Url};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use crate::{is_af_url, redirect_policy};
 
-                Err(Error::Unknown(format!(
-                    "unexpected json from server: {v:#?}"
-                )))
+    #[test]
+    fn af_urls() {
+        let url = |s| Url::parse(s).unwrap();
+
+        assert!(is_af_url(&url(
+            "https://www.afbostader.se/redimo/blueprint/1234.pdf"
+        )));
+        assert!(!is_af_url(&url("http://www.afbostader.se/x.pdf")));
+        assert!(!is_af_url(&url("https://www.afbostader.se.evil.com/x.pdf")));
+        assert!(!is_af_url(&url("https://169.254.169.254/latest/meta-data")));
+    }
+
+    #[tokio::test]
+    async fn redirects_stay_on_af() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let mut paths = Vec::new();
+
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0; 1024];
+                let n = stream.read(&mut buf).await.unwrap();
+                let req = String::from_utf8_lossy(&buf[..n]).into_owned();
+                let path = req.split(' ').nth(1).unwrap_or_default().to_owned();
+                let res = if path == "/redirect" {
+                    format!(
+                        "HTTP/1.1 302 Found\r\nLocation: http://{addr}/elsewhere\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    )
+                } else {
+                    "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned()
+                };
+                stream.write_all(res.as_bytes()).await.unwrap();
+                paths.push(path);
+
+                if paths.len() == 2 {
+                    break;
+                }
             }
-        }
+
+            paths
+        });
+
+        let client = reqwest::Client::builder()
+            .redirect(redirect_policy())
+            .build()
+            .unwrap();
+        let res = client
+            .get(format!("http://{addr}/redirect"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::FOUND);
+
+        // the server only sees the redirect and this request
+        client.get(format!("http://{addr}/")).send().await.unwrap();
+        assert_eq!(server.await.unwrap(), ["/redirect", "/"]);
     }
 }
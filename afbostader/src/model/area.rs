@@ -4,13 +4,61 @@ use serde_with::{serde_as, DisplayFromStr};
 
 #[serde_as]
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct Picture {
     #[serde_as(as = "DisplayFromStr")]
     pub url: Url,
     pub alt: Option<String>,
 }
 
+/// An area as listed on the AF Bostäder website.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct Area {
+    pub name: String,
+    pub slug: String,
+    #[serde_as(as = "DisplayFromStr")]
+    pub url: Url,
+}
+
+impl Area {
+    /// An area that is known only by name, e.g. from
+    /// [`Property::area`](crate::Property::area).
+    pub fn from_name(name: &str) -> Self {
+        let slug = crate::scrape::area_slug(name);
+
+        Self {
+            name: name.trim().to_owned(),
+            url: crate::scrape::area_url(&Url::parse(crate::WWW_AFBOSTADER_SE).unwrap(), &slug),
+            slug,
+        }
+    }
+
+    /// Check whether `name` (e.g. [`Property::area`](crate::Property::area))
+    /// refers to this area.
+    pub fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name.trim()) || self.slug == crate::scrape::area_slug(name)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct Contact {
+    pub lines: Vec<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct AreaDetail {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub facilities: Vec<String>,
+    /// Typical rents, as free-form text.
+    pub rents: Vec<String>,
+    pub addresses: Vec<String>,
+    pub contact: Option<Contact>,
     pub pictures: Vec<Picture>,
 }
@@ -10,6 +10,7 @@ use crate::model::yyyy_mm_dd;
 pub type PropertyId = u32;
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub enum PropertyType {
     Apartment,
     Dorm,
@@ -36,7 +37,9 @@ pub enum Priority {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct Property {
+    #[cfg_attr(feature = "utoipa", schema(value_type = u32))]
     pub id: PropertyId,
     pub description: String,
     pub short_description: String,
@@ -53,11 +56,19 @@ pub struct Property {
     pub reserve_until: Date,
     #[serde(with = "yyyy_mm_dd")]
     pub move_in: Date,
+    #[cfg_attr(feature = "utoipa", schema(value_type = Option<String>))]
     pub priority: Option<Priority>,
     pub rent: u32,
 }
 
+impl AsRef<Property> for Property {
+    fn as_ref(&self) -> &Property {
+        self
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct Address {
     pub street: String,
     pub city: String,
@@ -65,12 +76,14 @@ pub struct Address {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct QueuePosition {
     pub position: Option<u32>,
     pub total_in_queue: u32,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct Store {
     pub included: String,
     pub address: Option<String>,
@@ -79,6 +92,7 @@ pub struct Store {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct Worker {
     pub id: String,
     pub email: String,
@@ -89,6 +103,7 @@ pub struct Worker {
 
 #[serde_as]
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct PropertyDetail {
     #[serde(flatten)]
     pub property: Property,
@@ -107,3 +122,9 @@ pub struct PropertyDetail {
     #[serde_as(as = "Option<DisplayFromStr>")]
     pub blueprint: Option<Url>,
 }
+
+impl AsRef<Property> for PropertyDetail {
+    fn as_ref(&self) -> &Property {
+        &self.property
+    }
+}
@@ -4,17 +4,18 @@ use time::{format_description::BorrowedFormatItem, macros::format_description, D
 use crate::Address;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct User {
-    email: String,
-    personal_identity_number: Option<String>,
-    first_name: String,
-    last_name: String,
-    address: FullAddress,
-    mobile_phone: String,
-    start_year: Option<i32>,
-    start_semester: String,
+    pub email: String,
+    pub personal_identity_number: Option<String>,
+    pub first_name: String,
+    pub last_name: String,
+    pub address: FullAddress,
+    pub mobile_phone: String,
+    pub start_year: Option<i32>,
+    pub start_semester: String,
     #[serde(with = "super::yyyy_mm_dd::option")]
-    date_of_birth: Option<Date>,
+    pub date_of_birth: Option<Date>,
 }
 
 fn parse_dob(dob: &str) -> Option<Date> {
@@ -24,12 +25,13 @@ fn parse_dob(dob: &str) -> Option<Date> {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 pub struct FullAddress {
-    street: String,
-    city: String,
-    postal_code: String,
-    county: Option<String>,
-    country: String,
+    pub street: String,
+    pub city: String,
+    pub postal_code: String,
+    pub county: Option<String>,
+    pub country: String,
 }
 
 impl From<FullAddress> for Address {
@@ -0,0 +1,340 @@
+//! Scraping of the parts of www.afbostader.se that have no REST API.
+
+use reqwest::Url;
+use select::{
+    document::Document,
+    node::Node,
+    predicate::{Attr, Class, Name, Predicate},
+};
+
+use crate::{Area, AreaDetail, Contact, Error, Picture};
+
+const AREAS_PATH: &str = "/lediga-bostader/bostadsomraden/";
+
+/// Area names whose page is not found at the slugified name, either
+/// because the page is shared with another area or because the name used
+/// in the API differs from the one on the website.
+const AREA_ALIASES: &[(&str, &str)] = &[
+    ("parentesen", "sparta"),
+    ("michael-hansens-kollegium", "michael-hansen"),
+];
+
+/// The slug of the page describing an area.
+pub(crate) fn area_slug(name: &str) -> String {
+    let slug = slug::slugify(name);
+
+    AREA_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == slug)
+        .map_or(slug, |(_, slug)| (*slug).to_owned())
+}
+
+/// Collapse all whitespace into single spaces.
+fn clean(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn heading_level(node: &Node) -> Option<u8> {
+    match node.name()? {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        _ => None,
+    }
+}
+
+/// A heading and the content that follows it, up until the next heading
+/// of the same or a higher level.
+struct Section<'a> {
+    heading: String,
+    nodes: Vec<Node<'a>>,
+}
+
+impl<'a> Section<'a> {
+    fn paragraphs(&self) -> Vec<String> {
+        self.nodes
+            .iter()
+            .flat_map(|n| {
+                if n.name() == Some("p") {
+                    vec![*n]
+                } else {
+                    n.find(Name("p")).collect()
+                }
+            })
+            .map(|p| clean(&p.text()))
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+
+    fn list_items(&self) -> Vec<String> {
+        self.nodes
+            .iter()
+            .flat_map(|n| n.find(Name("li")))
+            .map(|li| clean(&li.text()))
+            .filter(|s| !s.is_empty())
+            .collect()
+    }
+
+    /// List items if there are any, otherwise paragraphs.
+    fn lines(&self) -> Vec<String> {
+        let items = self.list_items();
+
+        if items.is_empty() {
+            self.paragraphs()
+        } else {
+            items
+        }
+    }
+
+    fn links(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.nodes
+            .iter()
+            .flat_map(|n| {
+                if n.name() == Some("a") {
+                    vec![*n]
+                } else {
+                    n.find(Name("a")).collect()
+                }
+            })
+            .filter_map(|a| a.attr("href"))
+    }
+
+    fn is(&self, keywords: &[&str]) -> bool {
+        let heading = self.heading.to_lowercase();
+        keywords.iter().any(|k| heading.contains(k))
+    }
+}
+
+fn sections(doc: &Document) -> Vec<Section<'_>> {
+    doc.find(|n: &Node| heading_level(n).is_some_and(|l| l >= 2))
+        .map(|heading| {
+            let level = heading_level(&heading).unwrap();
+            let mut nodes = Vec::new();
+            let mut next = heading.next();
+
+            while let Some(node) = next {
+                if heading_level(&node).is_some_and(|l| l <= level) {
+                    break;
+                }
+                nodes.push(node);
+                next = node.next();
+            }
+
+            Section {
+                heading: clean(&heading.text()),
+                nodes,
+            }
+        })
+        .collect()
+}
+
+fn pictures(doc: &Document, base: &Url) -> Result<Vec<Picture>, Error> {
+    let slides = doc
+        .find(Class("slideshow").descendant(Class("slides")))
+        .next()
+        .ok_or(Error::Layout("no slideshow"))?;
+
+    Ok(slides
+        .find(Name("img"))
+        .filter_map(|node| {
+            let alt = node
+                .attr("alt")
+                .map(|s| s.trim_matches('\"'))
+                .filter(|s| !s.is_empty())
+                .map(ToOwned::to_owned);
+
+            Some(Picture {
+                alt,
+                url: base.join(node.attr("src")?).ok()?,
+            })
+        })
+        .collect())
+}
+
+fn contact(section: &Section) -> Contact {
+    let mut contact = Contact {
+        lines: section.paragraphs(),
+        email: None,
+        phone: None,
+    };
+
+    for href in section.links() {
+        if let Some(email) = href.strip_prefix("mailto:") {
+            contact.email.get_or_insert_with(|| email.trim().to_owned());
+        } else if let Some(phone) = href.strip_prefix("tel:") {
+            contact.phone.get_or_insert_with(|| phone.trim().to_owned());
+        }
+    }
+
+    contact
+}
+
+/// Parse an area page, such as
+/// <https://www.afbostader.se/lediga-bostader/bostadsomraden/delphi/>.
+///
+/// The page is mostly free-form text, so its contents are sorted by the
+/// headings they are found under.
+pub(crate) fn area_detail(html: &str, base: &Url) -> Result<AreaDetail, Error> {
+    let doc = Document::from(html);
+
+    let name = doc
+        .find(Name("h1"))
+        .map(|h| clean(&h.text()))
+        .find(|s| !s.is_empty());
+    let mut description = doc
+        .find(Name("meta").and(Attr("name", "description")))
+        .filter_map(|m| m.attr("content"))
+        .map(clean)
+        .find(|s| !s.is_empty());
+    let mut detail = AreaDetail {
+        name,
+        description: None,
+        facilities: Vec::new(),
+        rents: Vec::new(),
+        addresses: Vec::new(),
+        contact: None,
+        pictures: pictures(&doc, base)?,
+    };
+
+    for section in sections(&doc) {
+        if section.is(&["om området", "beskrivning"]) {
+            let text = section.paragraphs().join("\n\n");
+            if !text.is_empty() {
+                description = Some(text);
+            }
+        } else if section.is(&["facilitet", "service", "i området", "gemensam"]) {
+            detail.facilities.extend(section.lines());
+        } else if section.is(&["hyr"]) {
+            detail.rents.extend(section.lines());
+        } else if section.is(&["adress"]) {
+            detail.addresses.extend(section.lines());
+        } else if section.is(&["kontakt", "bovärd", "fastighetsskötare"]) {
+            detail.contact.get_or_insert_with(|| contact(&section));
+        }
+    }
+
+    detail.description = description;
+    Ok(detail)
+}
+
+/// The URL of the page describing an area.
+pub(crate) fn area_url(base: &Url, slug: &str) -> Url {
+    base.join(AREAS_PATH).unwrap().join(slug).unwrap()
+}
+
+/// Parse the list of areas at
+/// <https://www.afbostader.se/lediga-bostader/bostadsomraden/>.
+pub(crate) fn areas(html: &str, base: &Url) -> Result<Vec<Area>, Error> {
+    let doc = Document::from(html);
+    let mut areas: Vec<Area> = Vec::new();
+
+    for a in doc.find(Name("a")) {
+        let Some(url) = a.attr("href").and_then(|href| base.join(href).ok()) else {
+            continue;
+        };
+        let Some(slug) = url
+            .path()
+            .strip_prefix(AREAS_PATH)
+            .map(|s| s.trim_matches('/'))
+            .filter(|s| !s.is_empty() && !s.contains('/'))
+        else {
+            continue;
+        };
+        let name = clean(&a.text());
+
+        if name.is_empty() || areas.iter().any(|area| area.slug == slug) {
+            continue;
+        }
+
+        areas.push(Area {
+            slug: slug.to_owned(),
+            name,
+            url,
+        });
+    }
+
+    if areas.is_empty() {
+        return Err(Error::Layout("no areas listed"));
+    }
+
+    Ok(areas)
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Url;
+
+    use crate::Error;
+
+    fn base() -> Url {
+        Url::parse(crate::WWW_AFBOSTADER_SE).unwrap()
+    }
+
+    #[test]
+    fn area_slug() {
+        assert_eq!(super::area_slug("Delphi"), "delphi");
+        assert_eq!(super::area_slug("Kämnärsvägen"), "kamnarsvagen");
+        assert_eq!(super::area_slug("Parentesen"), "sparta");
+        assert_eq!(
+            super::area_slug("Michael Hansens kollegium"),
+            "michael-hansen"
+        );
+    }
+
+    #[test]
+    fn area_detail() {
+        let detail = super::area_detail(include_str!("area.html"), &base()).unwrap();
+
+        assert_eq!(detail.name.as_deref(), Some("Delphi"));
+        assert_eq!(
+            detail.description.as_deref(),
+            Some(
+                "Delphi ligger i norra Lund, nära LTH och Ideon.\n\n\
+                 Området består av 18 hus med korridorer och lägenheter."
+            )
+        );
+        assert_eq!(detail.facilities, ["Tvättstuga", "Cykelrum", "Bastu"]);
+        assert_eq!(detail.rents.len(), 2);
+        assert_eq!(
+            detail.addresses,
+            ["Magistratsvägen 10–56", "Professorsgatan 2–8"]
+        );
+
+        let contact = detail.contact.unwrap();
+        assert_eq!(contact.email.as_deref(), Some("delphi@afbostader.se"));
+        assert_eq!(contact.phone.as_deref(), Some("046-191500"));
+
+        assert_eq!(detail.pictures.len(), 2);
+        assert_eq!(
+            detail.pictures[0].url.as_str(),
+            "https://www.afbostader.se/globalassets/omraden/delphi/delphi-1.jpg"
+        );
+        assert_eq!(detail.pictures[0].alt.as_deref(), Some("Delphi från ovan"));
+        assert_eq!(detail.pictures[1].alt, None);
+    }
+
+    #[test]
+    fn area_without_slideshow() {
+        let html = include_str!("area.html").replace("slideshow", "carousel");
+
+        assert!(matches!(
+            super::area_detail(&html, &base()),
+            Err(Error::Layout(_))
+        ));
+    }
+
+    #[test]
+    fn areas() {
+        let areas = super::areas(include_str!("areas.html"), &base()).unwrap();
+        let slugs: Vec<_> = areas.iter().map(|a| a.slug.as_str()).collect();
+
+        assert_eq!(
+            slugs,
+            ["delphi", "sparta", "kamnarsvagen", "michael-hansen"]
+        );
+        assert_eq!(areas[0].name, "Delphi");
+        assert!(areas[3].matches("Michael Hansens kollegium"));
+        assert!(areas[1].matches("Parentesen"));
+    }
+}
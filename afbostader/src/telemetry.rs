@@ -0,0 +1,91 @@
+//! Metrics and spans for the calls made to AF, recorded with the
+//! [`metrics`] and [`tracing`] facades. Nothing is collected unless the
+//! application installs a recorder or subscriber.
+
+use std::{future::Future, time::Instant};
+
+use http::Extensions;
+use metrics::{counter, gauge, histogram};
+use reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next};
+use tracing::{field::Empty, info_span, Instrument};
+
+use crate::Error;
+
+/// Record the duration and outcome of a [`Client`](crate::Client) method,
+/// in a span of its own.
+pub(crate) async fn instrument<T>(
+    method: &'static str,
+    f: impl Future<Output = Result<T, Error>>,
+) -> Result<T, Error> {
+    let span = info_span!(
+        "af",
+        otel.name = format!("af.{method}"),
+        method,
+        error = Empty
+    );
+    let start = Instant::now();
+    let res = f.instrument(span.clone()).await;
+
+    if let Err(ref e) = res {
+        span.record("error", tracing::field::display(e));
+    }
+
+    histogram!("af_call_duration_seconds", "method" => method).record(start.elapsed());
+    counter!(
+        "af_calls_total",
+        "method" => method,
+        "outcome" => if res.is_ok() { "ok" } else { "error" },
+    )
+    .increment(1);
+
+    res
+}
+
+/// The number of vacancies in the latest listing.
+pub(crate) fn vacancies(n: usize) {
+    gauge!("af_vacancies").set(n as f64);
+}
+
+/// The number of times a request has been sent.
+#[derive(Clone, Copy)]
+struct Attempt(u32);
+
+/// Traces every attempt at sending a request and counts retries. Must be
+/// added after (i.e. inside) the retry middleware, which shares the
+/// extensions between attempts.
+pub(crate) struct TraceAttempts;
+
+#[async_trait::async_trait]
+impl Middleware for TraceAttempts {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let attempt = extensions.get::<Attempt>().map_or(1, |a| a.0 + 1);
+        extensions.insert(Attempt(attempt));
+
+        if attempt > 1 {
+            let host = req.url().host_str().unwrap_or_default().to_owned();
+            counter!("af_retries_total", "host" => host).increment(1);
+        }
+
+        let span = info_span!(
+            "af.request",
+            otel.name = format!("{} {}", req.method(), req.url().path()),
+            // without the query, which may be personal
+            url = %req.url().as_str().split('?').next().unwrap_or_default(),
+            attempt,
+            status = Empty,
+        );
+        let res = next.run(req, extensions).instrument(span.clone()).await;
+
+        if let Ok(ref res) = res {
+            span.record("status", res.status().as_u16());
+        }
+
+        res
+    }
+}
@@ -0,0 +1,279 @@
+//! Keeping the load on AF reasonable: a token bucket shared by every request
+//! and a circuit breaker that stops sending requests while AF is down.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use http::Extensions;
+use metrics::{counter, gauge};
+use reqwest::{Request, Response, StatusCode};
+use reqwest_middleware::{Middleware, Next};
+use tracing::{info, warn};
+
+/// How [`Client`](crate::Client) limits its requests to AF.
+#[derive(Debug, Clone, Copy)]
+pub struct ThrottleConfig {
+    /// Requests per second, on average.
+    pub rate: f64,
+    /// Requests that may be sent at once after a quiet period.
+    pub burst: u32,
+    /// Consecutive failures after which requests are refused.
+    pub failure_threshold: u32,
+    /// How long to refuse requests before trying AF again.
+    pub cooldown: Duration,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        Self {
+            rate: 5.0,
+            burst: 10,
+            failure_threshold: 5,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    rate: f64,
+    burst: f64,
+    /// Negative when requests are waiting for tokens.
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    /// Take a token, returning how long to wait before it may be used.
+    fn reserve(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst) - 1.0;
+        self.updated = now;
+
+        Duration::from_secs_f64((-self.tokens).max(0.0) / self.rate)
+    }
+}
+
+/// Delays requests so that at most [`ThrottleConfig::rate`] are sent per
+/// second. Must be added after (i.e. inside) the retry middleware, so that
+/// retries are throttled as well.
+pub(crate) struct RateLimit(Mutex<Bucket>);
+
+impl RateLimit {
+    pub(crate) fn new(config: &ThrottleConfig) -> Self {
+        Self(Mutex::new(Bucket {
+            rate: config.rate,
+            burst: config.burst.into(),
+            tokens: config.burst.into(),
+            updated: Instant::now(),
+        }))
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for RateLimit {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let wait = self.0.lock().unwrap().reserve(Instant::now());
+
+        if !wait.is_zero() {
+            counter!("af_throttled_total").increment(1);
+            tokio::time::sleep(wait).await;
+        }
+
+        next.run(req, extensions).await
+    }
+}
+
+/// Returned by the circuit breaker, and turned into
+/// [`Error::UpstreamUnavailable`](crate::Error::UpstreamUnavailable).
+#[derive(Debug, thiserror::Error)]
+#[error("circuit breaker is open")]
+pub(crate) struct CircuitOpen;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// A single request has been let through to see if AF is back.
+    HalfOpen {
+        since: Instant,
+    },
+}
+
+#[derive(Debug)]
+struct Breaker {
+    state: State,
+    failure_threshold: u32,
+    cooldown: Duration,
+}
+
+impl Breaker {
+    fn allow(&mut self, now: Instant) -> bool {
+        match self.state {
+            State::Closed { .. } => true,
+            State::Open { until } if now < until => false,
+            // a probe that never finished, e.g. because it was cancelled,
+            // must not keep the circuit half-open forever
+            State::HalfOpen { since } if now.duration_since(since) < self.cooldown => false,
+            State::Open { .. } | State::HalfOpen { .. } => {
+                self.state = State::HalfOpen { since: now };
+                true
+            }
+        }
+    }
+
+    fn record(&mut self, now: Instant, ok: bool) {
+        let open = State::Open {
+            until: now + self.cooldown,
+        };
+
+        self.state = match (self.state, ok) {
+            (State::Closed { .. }, true) => State::Closed { failures: 0 },
+            (_, true) => {
+                info!("AF is available again, closing circuit breaker");
+                State::Closed { failures: 0 }
+            }
+            (State::Closed { failures }, false) if failures + 1 < self.failure_threshold => {
+                State::Closed {
+                    failures: failures + 1,
+                }
+            }
+            (State::Closed { .. }, false) => {
+                warn!(
+                    "AF failed {} times in a row, opening circuit breaker",
+                    self.failure_threshold
+                );
+                open
+            }
+            (State::Open { until }, false) => State::Open { until },
+            (State::HalfOpen { .. }, false) => open,
+        };
+
+        gauge!("af_circuit_open").set(if matches!(self.state, State::Closed { .. }) {
+            0.0
+        } else {
+            1.0
+        });
+    }
+}
+
+/// Refuses to send requests after [`ThrottleConfig::failure_threshold`]
+/// consecutive failures, until a request sent after the cooldown succeeds.
+/// Must be added after (i.e. inside) the retry middleware, which gives up
+/// on the error it returns.
+pub(crate) struct CircuitBreaker(Mutex<Breaker>);
+
+impl CircuitBreaker {
+    pub(crate) fn new(config: &ThrottleConfig) -> Self {
+        Self(Mutex::new(Breaker {
+            state: State::Closed { failures: 0 },
+            failure_threshold: config.failure_threshold,
+            cooldown: config.cooldown,
+        }))
+    }
+}
+
+/// Whether a response means that AF is not working, as opposed to, for
+/// example, rejecting our credentials.
+fn is_failure(res: &reqwest_middleware::Result<Response>) -> bool {
+    match res {
+        Ok(res) => res.status().is_server_error() || res.status() == StatusCode::TOO_MANY_REQUESTS,
+        Err(reqwest_middleware::Error::Reqwest(_)) => true,
+        Err(reqwest_middleware::Error::Middleware(_)) => false,
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for CircuitBreaker {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        if !self.0.lock().unwrap().allow(Instant::now()) {
+            counter!("af_circuit_rejections_total").increment(1);
+            return Err(reqwest_middleware::Error::middleware(CircuitOpen));
+        }
+
+        let res = next.run(req, extensions).await;
+        self.0
+            .lock()
+            .unwrap()
+            .record(Instant::now(), !is_failure(&res));
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{Breaker, Bucket, State};
+
+    #[test]
+    fn bucket() {
+        let now = Instant::now();
+        let mut bucket = Bucket {
+            rate: 2.0,
+            burst: 2.0,
+            tokens: 2.0,
+            updated: now,
+        };
+
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::from_millis(500));
+        assert_eq!(bucket.reserve(now), Duration::from_secs(1));
+
+        // refills, but not beyond the burst
+        let later = now + Duration::from_secs(60);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+        assert_eq!(bucket.reserve(later), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn breaker() {
+        let cooldown = Duration::from_secs(30);
+        let now = Instant::now();
+        let mut breaker = Breaker {
+            state: State::Closed { failures: 0 },
+            failure_threshold: 3,
+            cooldown,
+        };
+
+        breaker.record(now, false);
+        breaker.record(now, true);
+        breaker.record(now, false);
+        breaker.record(now, false);
+        assert!(breaker.allow(now));
+        breaker.record(now, false);
+        assert!(!breaker.allow(now));
+
+        // one probe after the cooldown
+        let now = now + cooldown;
+        assert!(breaker.allow(now));
+        assert!(!breaker.allow(now));
+        breaker.record(now, false);
+        assert!(!breaker.allow(now));
+
+        let now = now + cooldown;
+        assert!(breaker.allow(now));
+        breaker.record(now, true);
+        assert!(breaker.allow(now));
+        assert!(breaker.allow(now));
+    }
+}
@@ -0,0 +1,603 @@
+//! TLS verification for AF Bostäder.
+//!
+//! AF serves an incomplete certificate chain, which only works in browsers
+//! that have cached (or go and fetch) the missing intermediates. We do the
+//! same: intermediates can be bundled or configured, and any that are still
+//! missing are fetched from the "CA Issuers" URL of the certificates AF
+//! sends. They are only used to complete the chains of the
+//! [`AF_HOSTS`](crate::AF_HOSTS), and must still lead to a trusted root.
+//! Everything else is verified as strictly as by any other client.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    future::Future,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::Duration,
+};
+
+use http::Extensions;
+use reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next};
+use ring::digest::{digest, SHA256};
+use rustls::{
+    client::{
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        WebPkiServerVerifier,
+    },
+    crypto::ring::default_provider,
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    CertificateError, ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use tokio::sync::OnceCell;
+use tracing::{info, warn};
+use x509_parser::{
+    extensions::{GeneralName, ParsedExtension},
+    oid_registry::OID_PKIX_ACCESS_DESCRIPTOR_CA_ISSUERS,
+};
+
+use crate::{AF_HOSTS, USER_AGENT};
+
+const BUNDLED_INTERMEDIATES: &str = include_str!("../certs/intermediates.pem");
+
+/// The SHA-256 fingerprint of a certificate, as printed by
+/// `openssl x509 -noout -fingerprint -sha256`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint([u8; 32]);
+
+impl Fingerprint {
+    pub fn of(cert: &[u8]) -> Self {
+        Self(digest(&SHA256, cert).as_ref().try_into().unwrap())
+    }
+}
+
+impl fmt::Debug for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, b) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(":")?;
+            }
+            write!(f, "{b:02X}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("expected 32 hexadecimal bytes, optionally separated by colons")]
+pub struct ParseFingerprintError;
+
+impl FromStr for Fingerprint {
+    type Err = ParseFingerprintError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.trim().replace(':', "");
+
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err(ParseFingerprintError);
+        }
+
+        let mut bytes = [0; 32];
+        for (i, b) in bytes.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)
+                .map_err(|_| ParseFingerprintError)?;
+        }
+
+        Ok(Self(bytes))
+    }
+}
+
+/// How to verify the certificates of the [`AF_HOSTS`](crate::AF_HOSTS).
+#[derive(Debug, Clone, Default)]
+pub struct TlsConfig {
+    /// Intermediates to use in addition to the bundled ones, in case AF
+    /// changes certificate authority before we get around to updating.
+    pub intermediates: Vec<CertificateDer<'static>>,
+    /// If not empty, the chain sent by AF must contain a certificate with
+    /// one of these fingerprints. Pinning an intermediate rather than the
+    /// leaf certificate survives renewals.
+    pub pins: Vec<Fingerprint>,
+}
+
+impl TlsConfig {
+    /// Parse PEM-encoded intermediates, ignoring anything that is not a
+    /// certificate.
+    pub fn parse_intermediates(pem: &[u8]) -> std::io::Result<Vec<CertificateDer<'static>>> {
+        rustls_pemfile::certs(&mut &*pem).collect()
+    }
+
+    /// The TLS configuration, and the intermediates that it fetches, which
+    /// [`CompleteChain`] does the fetching of.
+    pub(crate) fn build(self) -> (ClientConfig, Arc<Fetched>) {
+        let roots = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        let fetched = Arc::new(Fetched::default());
+        let verifier = self.verifier(roots, fetched.clone());
+
+        let config = ClientConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(verifier))
+            .with_no_client_auth();
+
+        (config, fetched)
+    }
+
+    fn verifier(self, roots: RootCertStore, fetched: Arc<Fetched>) -> AfVerifier {
+        let inner = WebPkiServerVerifier::builder_with_provider(
+            Arc::new(roots),
+            Arc::new(default_provider()),
+        )
+        .build()
+        .unwrap();
+        let mut intermediates =
+            Self::parse_intermediates(BUNDLED_INTERMEDIATES.as_bytes()).unwrap();
+        intermediates.extend(self.intermediates);
+
+        AfVerifier {
+            inner,
+            intermediates,
+            pins: self.pins,
+            fetched,
+        }
+    }
+}
+
+/// The largest intermediate certificate that is downloaded.
+const MAX_INTERMEDIATE_SIZE: usize = 64 * 1024;
+
+/// Intermediates fetched from "CA Issuers" URLs, shared by the verifier,
+/// which asks for them, and [`CompleteChain`], which fetches them.
+#[derive(Debug, Default)]
+pub(crate) struct Fetched {
+    certs: RwLock<Vec<CertificateDer<'static>>>,
+    /// How many times certificates have been added, for a request to tell
+    /// whether it is worth sending again.
+    added: AtomicUsize,
+    /// URLs of intermediates that chains were missing, until they have
+    /// been fetched. Every failed request helps fetch all of them, so a
+    /// concurrent request can't take a URL that another one is waiting for.
+    missing: Mutex<HashSet<String>>,
+    /// Each URL is only fetched once, by whichever request gets there
+    /// first, so that a URL that doesn't help isn't tried again.
+    fetches: Mutex<HashMap<String, Arc<OnceCell<()>>>>,
+}
+
+impl Fetched {
+    /// Ask for the issuer of the first of `certs` whose issuer hasn't been
+    /// fetched already.
+    fn want<'a>(&self, certs: impl IntoIterator<Item = &'a CertificateDer<'a>>) {
+        let fetches = self.fetches.lock().unwrap();
+
+        if let Some(url) = certs
+            .into_iter()
+            .filter_map(ca_issuers_url)
+            .find(|url| !fetches.contains_key(url))
+        {
+            self.missing.lock().unwrap().insert(url);
+        }
+    }
+
+    fn added(&self) -> usize {
+        self.added.load(Ordering::SeqCst)
+    }
+
+    /// Fetch the missing intermediates with `fetch`, or wait for the
+    /// requests that are already fetching them, returning whether any
+    /// certificates have been added `since`.
+    async fn complete<F, Fut>(&self, since: usize, fetch: F) -> bool
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<Vec<u8>, reqwest::Error>>,
+    {
+        // including those that other requests are fetching
+        let mut urls: HashSet<String> = self.fetches.lock().unwrap().keys().cloned().collect();
+        urls.extend(self.missing.lock().unwrap().iter().cloned());
+
+        for url in urls {
+            let cell = self
+                .fetches
+                .lock()
+                .unwrap()
+                .entry(url.clone())
+                .or_default()
+                .clone();
+
+            cell.get_or_init(|| async {
+                match fetch(url.clone()).await {
+                    Ok(file) => match self.add(&file) {
+                        0 => warn!("no certificate found at {url}"),
+                        n => info!("fetched {n} intermediate certificate(s) for AF from {url}"),
+                    },
+                    Err(e) => warn!("failed to fetch intermediate certificate from {url}: {e}"),
+                }
+                self.missing.lock().unwrap().remove(&url);
+            })
+            .await;
+        }
+
+        self.added() != since
+    }
+
+    /// Add the certificates in a DER or PEM file, returning how many there
+    /// were.
+    fn add(&self, file: &[u8]) -> usize {
+        let certs = if file.starts_with(b"-----BEGIN") {
+            TlsConfig::parse_intermediates(file).unwrap_or_default()
+        } else {
+            vec![CertificateDer::from(file.to_vec())]
+        };
+        let certs: Vec<_> = certs
+            .into_iter()
+            .filter(|cert| x509_parser::parse_x509_certificate(cert).is_ok())
+            .collect();
+        let n = certs.len();
+
+        if n > 0 {
+            self.certs.write().unwrap().extend(certs);
+            self.added.fetch_add(1, Ordering::SeqCst);
+        }
+
+        n
+    }
+}
+
+/// Where to get the certificate of the issuer of `cert`, according to its
+/// Authority Information Access extension.
+fn ca_issuers_url(cert: &CertificateDer) -> Option<String> {
+    let (_, cert) = x509_parser::parse_x509_certificate(cert).ok()?;
+
+    cert.extensions()
+        .iter()
+        .find_map(|ext| match ext.parsed_extension() {
+            ParsedExtension::AuthorityInfoAccess(aia) => Some(aia),
+            _ => None,
+        })?
+        .accessdescs
+        .iter()
+        .filter(|desc| desc.access_method == OID_PKIX_ACCESS_DESCRIPTOR_CA_ISSUERS)
+        .find_map(|desc| match desc.access_location {
+            GeneralName::URI(uri) => Some(uri.to_owned()),
+            _ => None,
+        })
+}
+
+/// Fetches the intermediates that the verifier found missing and sends a
+/// failed request again once they have been added. Must be added after
+/// (i.e. inside) the retry middleware, so that the request isn't retried
+/// before the intermediate is there.
+pub(crate) struct CompleteChain {
+    fetched: Arc<Fetched>,
+    client: reqwest::Client,
+}
+
+impl CompleteChain {
+    pub(crate) fn new(fetched: Arc<Fetched>) -> Self {
+        Self {
+            fetched,
+            client: reqwest::Client::builder()
+                .user_agent(USER_AGENT)
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap(),
+        }
+    }
+
+    /// Download an intermediate, which is empty if it is too large.
+    async fn download(&self, url: String) -> Result<Vec<u8>, reqwest::Error> {
+        let mut res = self.client.get(url).send().await?.error_for_status()?;
+        let mut file = Vec::new();
+
+        while let Some(chunk) = res.chunk().await? {
+            if file.len() + chunk.len() > MAX_INTERMEDIATE_SIZE {
+                return Ok(Vec::new());
+            }
+            file.extend_from_slice(&chunk);
+        }
+
+        Ok(file)
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for CompleteChain {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let again = req.try_clone();
+        let since = self.fetched.added();
+        let res = next.clone().run(req, extensions).await;
+
+        if res.is_ok() {
+            return res;
+        }
+
+        if self.fetched.complete(since, |url| self.download(url)).await {
+            if let Some(again) = again {
+                return next.run(again, extensions).await;
+            }
+        }
+
+        res
+    }
+}
+
+#[derive(Debug)]
+struct AfVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    intermediates: Vec<CertificateDer<'static>>,
+    pins: Vec<Fingerprint>,
+    fetched: Arc<Fetched>,
+}
+
+fn is_af_host(server_name: &ServerName) -> bool {
+    match server_name {
+        ServerName::DnsName(name) => AF_HOSTS.contains(&name.as_ref()),
+        _ => false,
+    }
+}
+
+impl ServerCertVerifier for AfVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if !is_af_host(server_name) {
+            return self.inner.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                now,
+            );
+        }
+
+        // only what the server sent can be pinned; our own intermediates
+        // would always match
+        if !self.pins.is_empty()
+            && !std::iter::once(end_entity)
+                .chain(intermediates)
+                .any(|cert| self.pins.contains(&Fingerprint::of(cert)))
+        {
+            return Err(rustls::Error::General(format!(
+                "no certificate from {server_name:?} matches the pinned fingerprints"
+            )));
+        }
+
+        let mut chain = intermediates.to_vec();
+        chain.extend(self.intermediates.iter().cloned());
+        chain.extend(self.fetched.certs.read().unwrap().iter().cloned());
+
+        let res =
+            self.inner
+                .verify_server_cert(end_entity, &chain, server_name, ocsp_response, now);
+
+        if let Err(rustls::Error::InvalidCertificate(CertificateError::UnknownIssuer)) = res {
+            self.fetched.want(std::iter::once(end_entity).chain(&chain));
+        }
+
+        res
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use rcgen::{BasicConstraints, CertificateParams, CustomExtension, DnType, IsCa, KeyPair};
+    use rustls::{
+        client::danger::{ServerCertVerified, ServerCertVerifier},
+        pki_types::{CertificateDer, ServerName, UnixTime},
+        CertificateError, RootCertStore,
+    };
+
+    use super::{AfVerifier, Fetched, Fingerprint, TlsConfig};
+
+    #[test]
+    fn fingerprint() {
+        let hex = "AB:CD:EF:01:23:45:67:89:AB:CD:EF:01:23:45:67:89:\
+                   AB:CD:EF:01:23:45:67:89:AB:CD:EF:01:23:45:67:89";
+        let fingerprint: Fingerprint = hex.parse().unwrap();
+
+        assert_eq!(format!("{fingerprint:?}"), hex);
+        assert_eq!(
+            hex.replace(':', "").parse::<Fingerprint>().unwrap(),
+            fingerprint
+        );
+        assert!("AB:CD".parse::<Fingerprint>().is_err());
+        assert!(hex.replace('A', "G").parse::<Fingerprint>().is_err());
+    }
+
+    #[test]
+    fn bundled_intermediates() {
+        // must at least parse, or every client would panic on creation
+        super::TlsConfig::default().build();
+    }
+
+    /// A DER element with a short length.
+    fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+        assert!(content.len() < 128);
+        [&[tag, content.len() as u8], content].concat()
+    }
+
+    /// A root, an intermediate and a leaf for the AF hosts, whose "CA
+    /// Issuers" URL is `url`.
+    fn chain(url: &str) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let ca = |name: &str| {
+            let mut params = CertificateParams::new(Vec::new()).unwrap();
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            params.distinguished_name.push(DnType::CommonName, name);
+            params
+        };
+
+        let root_key = KeyPair::generate().unwrap();
+        let root = ca("Root").self_signed(&root_key).unwrap();
+        let intermediate_key = KeyPair::generate().unwrap();
+        let intermediate = ca("Intermediate")
+            .signed_by(&intermediate_key, &root, &root_key)
+            .unwrap();
+
+        // id-pe-authorityInfoAccess, with an id-ad-caIssuers URI
+        let ca_issuers = [0x06, 0x08, 0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x02];
+        let aia = der(
+            0x30,
+            &der(
+                0x30,
+                &[&ca_issuers[..], &der(0x86, url.as_bytes())].concat(),
+            ),
+        );
+        let mut params = CertificateParams::new(vec!["www.afbostader.se".to_owned()]).unwrap();
+        params
+            .custom_extensions
+            .push(CustomExtension::from_oid_content(
+                &[1, 3, 6, 1, 5, 5, 7, 1, 1],
+                aia,
+            ));
+        let leaf = params
+            .signed_by(
+                &KeyPair::generate().unwrap(),
+                &intermediate,
+                &intermediate_key,
+            )
+            .unwrap();
+
+        (
+            root.der().to_vec(),
+            intermediate.der().to_vec(),
+            leaf.der().to_vec(),
+        )
+    }
+
+    fn verifier(root: &[u8]) -> (AfVerifier, Arc<Fetched>) {
+        let mut roots = RootCertStore::empty();
+        roots.add(CertificateDer::from(root.to_vec())).unwrap();
+        let fetched = Arc::new(Fetched::default());
+
+        (
+            TlsConfig::default().verifier(roots, fetched.clone()),
+            fetched,
+        )
+    }
+
+    fn verify(
+        verifier: &AfVerifier,
+        leaf: &[u8],
+        host: &'static str,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        verifier.verify_server_cert(
+            &CertificateDer::from(leaf),
+            &[],
+            &ServerName::try_from(host).unwrap(),
+            &[],
+            UnixTime::now(),
+        )
+    }
+
+    fn missing(fetched: &Fetched) -> Vec<String> {
+        fetched.missing.lock().unwrap().iter().cloned().collect()
+    }
+
+    #[tokio::test]
+    async fn fetches_missing_intermediates() {
+        let url = "http://ca.example.com/intermediate.crt";
+        let (root, intermediate, leaf) = chain(url);
+        let (verifier, fetched) = verifier(&root);
+
+        // other hosts must send their own intermediates
+        assert!(verify(&verifier, &leaf, "example.com").is_err());
+        assert!(missing(&fetched).is_empty());
+
+        assert!(matches!(
+            verify(&verifier, &leaf, "www.afbostader.se"),
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::UnknownIssuer
+            ))
+        ));
+        assert_eq!(missing(&fetched), [url]);
+
+        // a URL that doesn't help is only fetched once
+        let since = fetched.added();
+        let useless = |_| async { Ok(b"not a certificate".to_vec()) };
+        assert!(!fetched.complete(since, useless).await);
+        assert!(verify(&verifier, &leaf, "www.afbostader.se").is_err());
+        assert!(missing(&fetched).is_empty());
+
+        assert_eq!(fetched.add(&intermediate), 1);
+        verify(&verifier, &leaf, "www.afbostader.se").unwrap();
+        assert!(verify(&verifier, &leaf, "example.com").is_err());
+    }
+
+    #[tokio::test]
+    async fn concurrent_handshakes() {
+        let url = "http://ca.example.com/intermediate.crt";
+        let (root, intermediate, leaf) = chain(url);
+        let (verifier, fetched) = verifier(&root);
+        let downloads = AtomicUsize::new(0);
+        let download = |_| {
+            downloads.fetch_add(1, Ordering::SeqCst);
+            let intermediate = intermediate.clone();
+
+            async move {
+                tokio::task::yield_now().await;
+                Ok(intermediate)
+            }
+        };
+
+        // two requests fail before either of them gets to fetch
+        let since = fetched.added();
+        assert!(verify(&verifier, &leaf, "www.afbostader.se").is_err());
+        assert!(verify(&verifier, &leaf, "www.afbostader.se").is_err());
+
+        let (a, b) = tokio::join!(
+            fetched.complete(since, download),
+            fetched.complete(since, download),
+        );
+        assert!(a && b, "both requests are sent again");
+        assert_eq!(downloads.load(Ordering::SeqCst), 1);
+        verify(&verifier, &leaf, "www.afbostader.se").unwrap();
+
+        // a request that fails after another one has fetched the
+        // intermediate is sent again too
+        assert!(fetched.complete(since, download).await);
+        assert_eq!(downloads.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,328 @@
+//! iCalendar feeds of when vacancies can be reserved and moved into, for
+//! subscribing to in a calendar app.
+//!
+//! Calendar apps can neither send cookies nor be given a long query string
+//! without it getting lost, so a filter can also be saved under a secret
+//! token that makes up a short URL. Anybody can save one, so saving is
+//! limited per IP address (or IPv6 /64, see [`crate::limit::client_key`]),
+//! filters that are too long to be real are refused, calendars that no app
+//! has fetched in a while are removed, and once there are too many the
+//! least recently fetched one makes way for a new one.
+
+use std::fmt::Write;
+
+use afbostader::Property;
+use rand::RngCore;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use time::{Date, Duration, OffsetDateTime};
+
+use crate::{
+    db::{Db, DbError, CALENDARS},
+    filter::VacancyFilter,
+};
+
+/// Lines are folded after this many bytes, as RFC 5545 requires.
+const MAX_LINE_LEN: usize = 75;
+
+/// How often calendar apps are asked to check for new vacancies.
+const REFRESH_INTERVAL: &str = "PT6H";
+
+/// Saved calendars that haven't been fetched for this long are removed.
+const EXPIRY: Duration = Duration::days(180);
+
+/// When a calendar was last fetched is only updated this often, rather
+/// than writing to the database every time.
+const TOUCH_INTERVAL: Duration = Duration::days(1);
+
+/// At most this many calendars are saved at once; saving another removes
+/// the one that was fetched least recently.
+pub const MAX_CALENDARS: u64 = 10_000;
+
+/// Calendars that one IP address can save per hour.
+pub const SAVES_PER_HOUR: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Saved {
+    filter: VacancyFilter,
+    /// When the calendar was last fetched, as a Unix timestamp.
+    last_used: i64,
+}
+
+impl Saved {
+    fn is_expired(&self, now: OffsetDateTime) -> bool {
+        now.unix_timestamp() - self.last_used > EXPIRY.whole_seconds()
+    }
+}
+
+/// Save a filter, returning the token to get its calendar with.
+pub async fn save(db: &Db, filter: &VacancyFilter, now: OffsetDateTime) -> Result<String, DbError> {
+    save_within(db, filter, now, MAX_CALENDARS).await
+}
+
+async fn save_within(
+    db: &Db,
+    filter: &VacancyFilter,
+    now: OffsetDateTime,
+    max: u64,
+) -> Result<String, DbError> {
+    if db.len(CALENDARS).await? >= max {
+        db.remove_min_by_key(CALENDARS, |saved: &Saved| saved.last_used)
+            .await?;
+    }
+
+    let mut bytes = [0; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let token = bytes.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    });
+
+    let saved = Saved {
+        filter: filter.clone(),
+        last_used: now.unix_timestamp(),
+    };
+    db.insert(CALENDARS, &token, &saved).await?;
+
+    Ok(token)
+}
+
+/// The filter saved under `token`, if any, noting that it is in use.
+pub async fn load(
+    db: &Db,
+    token: &str,
+    now: OffsetDateTime,
+) -> Result<Option<VacancyFilter>, DbError> {
+    let Some(mut saved) = db.get::<Saved>(CALENDARS, token).await? else {
+        return Ok(None);
+    };
+
+    if saved.is_expired(now) {
+        return Ok(None);
+    }
+    if now.unix_timestamp() - saved.last_used > TOUCH_INTERVAL.whole_seconds() {
+        saved.last_used = now.unix_timestamp();
+        db.insert(CALENDARS, token, &saved).await?;
+    }
+
+    Ok(Some(saved.filter))
+}
+
+/// Remove calendars that haven't been fetched in a while, returning how
+/// many were removed.
+pub async fn prune(db: &Db, now: OffsetDateTime) -> Result<u64, DbError> {
+    db.retain(CALENDARS, move |_, saved: Saved| !saved.is_expired(now))
+        .await
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Append a content line, folded so that no line is longer than
+/// [`MAX_LINE_LEN`] bytes.
+fn line(out: &mut String, line: &str) {
+    let mut len = 0;
+
+    for c in line.chars() {
+        if len + c.len_utf8() > MAX_LINE_LEN {
+            out.push_str("\r\n ");
+            // the space counts
+            len = 1;
+        }
+        out.push(c);
+        len += c.len_utf8();
+    }
+
+    out.push_str("\r\n");
+}
+
+fn date(d: Date) -> String {
+    format!("{:04}{:02}{:02}", d.year(), u8::from(d.month()), d.day())
+}
+
+fn event(
+    out: &mut String,
+    uid: &str,
+    stamp: &str,
+    (start, end): (Date, Date),
+    summary: &str,
+    description: &str,
+    url: &Url,
+) {
+    line(out, "BEGIN:VEVENT");
+    line(out, &format!("UID:{uid}"));
+    line(out, &format!("DTSTAMP:{stamp}"));
+    line(out, &format!("DTSTART;VALUE=DATE:{}", date(start)));
+    // all-day events end the day after
+    line(
+        out,
+        &format!("DTEND;VALUE=DATE:{}", date(end + Duration::days(1))),
+    );
+    line(out, &format!("SUMMARY:{}", escape(summary)));
+    line(out, &format!("DESCRIPTION:{}", escape(description)));
+    line(out, &format!("URL:{url}"));
+    line(out, "TRANSP:TRANSPARENT");
+    line(out, "END:VEVENT");
+}
+
+/// A calendar with the reservation window and move-in date of every
+/// vacancy, linking to their pages on the frontend.
+pub fn render(vacancies: &[Property], frontend: &Url, now: OffsetDateTime) -> String {
+    let now = now.to_offset(time::UtcOffset::UTC);
+    let stamp = format!(
+        "{}T{:02}{:02}{:02}Z",
+        date(now.date()),
+        now.hour(),
+        now.minute(),
+        now.second()
+    );
+    let host = frontend.host_str().unwrap_or("localhost");
+    let mut out = String::new();
+
+    line(&mut out, "BEGIN:VCALENDAR");
+    line(&mut out, "VERSION:2.0");
+    line(&mut out, "PRODID:-//amcoff//bostader//SV");
+    line(&mut out, "CALSCALE:GREGORIAN");
+    line(&mut out, "METHOD:PUBLISH");
+    line(&mut out, "X-WR-CALNAME:AF Bostäder");
+    line(
+        &mut out,
+        &format!("REFRESH-INTERVAL;VALUE=DURATION:{REFRESH_INTERVAL}"),
+    );
+    line(&mut out, &format!("X-PUBLISHED-TTL:{REFRESH_INTERVAL}"));
+
+    for p in vacancies {
+        let url = frontend
+            .join(&format!("bostad/{}", p.id))
+            .unwrap_or_else(|_| frontend.clone());
+        let description = format!(
+            "{}\n{} kr/mån, {} m², {}\n{url}",
+            p.short_description, p.rent, p.size_sqm, p.area
+        );
+
+        event(
+            &mut out,
+            &format!("{}-reservation@{host}", p.id),
+            &stamp,
+            (p.reserve_from, p.reserve_until),
+            &format!("Intresseanmälan: {}", p.address.street),
+            &description,
+            &url,
+        );
+        event(
+            &mut out,
+            &format!("{}-move-in@{host}", p.id),
+            &stamp,
+            (p.move_in, p.move_in),
+            &format!("Inflyttning: {}", p.address.street),
+            &description,
+            &url,
+        );
+    }
+
+    line(&mut out, "END:VCALENDAR");
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Url;
+    use time::{Duration, OffsetDateTime};
+
+    use super::{
+        escape, load, prune, render, save, save_within, EXPIRY, MAX_LINE_LEN, TOUCH_INTERVAL,
+    };
+    use crate::{db::Db, filter::VacancyFilter, testing};
+
+    #[tokio::test]
+    async fn saved_calendars_expire() {
+        let db = Db::in_memory().unwrap();
+        let now = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let filter = VacancyFilter {
+            max_rent: Some(5000),
+            ..Default::default()
+        };
+
+        let old = save(&db, &filter, now).await.unwrap();
+        let used = save(&db, &filter, now).await.unwrap();
+        assert_ne!(old, used);
+
+        let later = now + EXPIRY / 2;
+        let filter = load(&db, &used, later).await.unwrap().unwrap();
+        assert_eq!(filter.max_rent, Some(5000));
+
+        // only the calendar that was fetched halfway is kept
+        let later = now + EXPIRY + Duration::days(1);
+        assert!(load(&db, &old, later).await.unwrap().is_none());
+        assert_eq!(prune(&db, later).await.unwrap(), 1);
+        assert!(load(&db, &used, later).await.unwrap().is_some());
+        assert!(load(&db, "unknown", later).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn least_recently_used_makes_way() {
+        let db = Db::in_memory().unwrap();
+        let now = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let filter = VacancyFilter::default();
+
+        let first = save_within(&db, &filter, now, 2).await.unwrap();
+        let second = save_within(&db, &filter, now, 2).await.unwrap();
+        // the first is fetched after the second was saved
+        let later = now + TOUCH_INTERVAL + Duration::days(1);
+        load(&db, &first, later).await.unwrap().unwrap();
+
+        let third = save_within(&db, &filter, later, 2).await.unwrap();
+        assert!(load(&db, &first, later).await.unwrap().is_some());
+        assert!(load(&db, &second, later).await.unwrap().is_none());
+        assert!(load(&db, &third, later).await.unwrap().is_some());
+    }
+
+    #[test]
+    fn calendar() {
+        let property = testing::property();
+        let frontend = Url::parse("https://bostader.example.com/").unwrap();
+
+        let ics = render(
+            std::slice::from_ref(&property),
+            &frontend,
+            OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap(),
+        );
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+        assert!(ics.contains("DTSTAMP:20231114T221320Z\r\n"));
+        assert!(ics.contains(&format!(
+            "UID:{}-reservation@bostader.example.com\r\n",
+            property.id
+        )));
+        assert!(ics.contains(&format!(
+            "URL:https://bostader.example.com/bostad/{}\r\n",
+            property.id
+        )));
+        assert!(ics
+            .split("\r\n")
+            .all(|line| line.len() <= MAX_LINE_LEN && !line.contains('\n')));
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(escape("a;b,c\\d\r\ne"), "a\\;b\\,c\\\\d\\ne");
+    }
+}
@@ -0,0 +1,402 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_graphql::SimpleObject;
+use axum::async_trait;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::{db::DbError, geocode::Place};
+
+mod osrm;
+
+pub use osrm::Osrm;
+
+/// Mean earth radius in meters.
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// How many routes are looked up in the background at once.
+const MAX_CONCURRENT_LOOKUPS: usize = 4;
+
+/// How long to use the estimate after looking up a route has failed,
+/// before trying again.
+const RETRY_AFTER: Duration = Duration::from_secs(10 * 60);
+
+/// A place that students commute to, such as LTH or Lund C.
+#[derive(Debug, Clone)]
+pub struct PointOfInterest {
+    pub name: String,
+    pub place: Place,
+}
+
+impl FromStr for PointOfInterest {
+    type Err = String;
+
+    /// Parse `"LTH=55.7110,13.2100"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, coords) = s.split_once('=').ok_or("expected NAME=LAT,LON")?;
+        let (lat, lon) = coords.split_once(',').ok_or("expected NAME=LAT,LON")?;
+        let lat = lat.trim().parse().map_err(|_| "invalid latitude")?;
+        let lon = lon.trim().parse().map_err(|_| "invalid longitude")?;
+
+        Ok(Self {
+            name: name.trim().to_owned(),
+            place: Place { lat, lon },
+        })
+    }
+}
+
+/// The great-circle distance between two places, in meters.
+pub fn haversine(a: Place, b: Place) -> f64 {
+    let (lat_a, lat_b) = (a.lat.to_radians(), b.lat.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (b.lon - a.lon).to_radians();
+
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS * h.sqrt().asin()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    Walking,
+    Cycling,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, SimpleObject)]
+pub struct Route {
+    pub distance_m: f64,
+    pub duration_s: f64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RouteError {
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    Db(#[from] DbError),
+}
+
+/// A key for the route between two places that is the same for places
+/// within about a metre of each other.
+pub(crate) fn cache_key(from: Place, to: Place, mode: Mode) -> String {
+    format!(
+        "{mode:?}|{:.5},{:.5}|{:.5},{:.5}",
+        from.lat, from.lon, to.lat, to.lon
+    )
+}
+
+/// Something that finds the way between two places.
+#[async_trait]
+pub trait RouteProvider: Send + Sync {
+    async fn route(&self, from: Place, to: Place, mode: Mode) -> Result<Option<Route>, RouteError>;
+
+    /// The route, if it can be had without asking anybody, e.g. because it
+    /// has been looked up before. The outer `None` means that it can't.
+    async fn cached(
+        &self,
+        from: Place,
+        to: Place,
+        mode: Mode,
+    ) -> Result<Option<Option<Route>>, RouteError> {
+        let _ = (from, to, mode);
+
+        Ok(None)
+    }
+}
+
+/// A router that needs no routing engine: it assumes that the way is a bit
+/// longer than the straight line, travelled at a typical speed.
+#[derive(Debug, Default)]
+pub struct Estimate;
+
+impl Estimate {
+    /// How much longer than the straight line the roads usually are.
+    const DETOUR_FACTOR: f64 = 1.3;
+
+    fn estimate(from: Place, to: Place, mode: Mode) -> Route {
+        let speed = match mode {
+            Mode::Walking => 5.0 / 3.6,
+            Mode::Cycling => 15.0 / 3.6,
+        };
+        let distance_m = haversine(from, to) * Self::DETOUR_FACTOR;
+
+        Route {
+            distance_m,
+            duration_s: distance_m / speed,
+        }
+    }
+}
+
+#[async_trait]
+impl RouteProvider for Estimate {
+    async fn route(&self, from: Place, to: Place, mode: Mode) -> Result<Option<Route>, RouteError> {
+        Ok(Some(Self::estimate(from, to, mode)))
+    }
+
+    async fn cached(
+        &self,
+        from: Place,
+        to: Place,
+        mode: Mode,
+    ) -> Result<Option<Option<Route>>, RouteError> {
+        Ok(Some(Some(Self::estimate(from, to, mode))))
+    }
+}
+
+/// The way from a vacancy to a [`PointOfInterest`].
+#[derive(Debug, Clone, Serialize, ToSchema, SimpleObject)]
+pub struct Commute {
+    pub to: String,
+    pub straight_line_m: f64,
+    pub walking: Option<Route>,
+    pub cycling: Option<Route>,
+}
+
+impl Commute {
+    /// What to sort by for the given mode; `None` means the straight line.
+    pub fn sort_key(&self, mode: Option<Mode>) -> Option<f64> {
+        match mode {
+            None => Some(self.straight_line_m),
+            Some(Mode::Walking) => self.walking.map(|r| r.duration_s),
+            Some(Mode::Cycling) => self.cycling.map(|r| r.duration_s),
+        }
+    }
+}
+
+/// Routes being looked up in the background.
+struct Lookups {
+    permits: Semaphore,
+    /// When the lookup of each route failed, or `None` while it is under
+    /// way.
+    pending: Mutex<HashMap<String, Option<Instant>>>,
+}
+
+/// The commutes from vacancies to the points of interest.
+///
+/// Routes that the router doesn't know yet are estimated, so that listing
+/// vacancies never waits for a routing engine, and looked up in the
+/// background for next time.
+#[derive(Clone)]
+pub struct Commutes {
+    pois: Arc<[PointOfInterest]>,
+    router: Arc<dyn RouteProvider>,
+    lookups: Arc<Lookups>,
+}
+
+impl Commutes {
+    pub fn new(pois: Vec<PointOfInterest>, router: Arc<dyn RouteProvider>) -> Self {
+        Self {
+            pois: pois.into(),
+            router,
+            lookups: Arc::new(Lookups {
+                permits: Semaphore::new(MAX_CONCURRENT_LOOKUPS),
+                pending: Default::default(),
+            }),
+        }
+    }
+
+    pub fn points_of_interest(&self) -> &[PointOfInterest] {
+        &self.pois
+    }
+
+    /// The route if the router knows it already, or else an estimate.
+    async fn route(&self, from: Place, to: Place, mode: Mode) -> Option<Route> {
+        match self.router.cached(from, to, mode).await {
+            Ok(Some(route)) => return route,
+            Ok(None) => self.look_up(from, to, mode),
+            Err(e) => warn!("reading cached route failed: {e}"),
+        }
+
+        Some(Estimate::estimate(from, to, mode))
+    }
+
+    /// Look up a route in the background, unless it is being looked up
+    /// already or failed recently.
+    fn look_up(&self, from: Place, to: Place, mode: Mode) {
+        let key = cache_key(from, to, mode);
+
+        {
+            let mut pending = self.lookups.pending.lock().unwrap();
+            pending.retain(|_, failed| failed.is_none_or(|t| t.elapsed() < RETRY_AFTER));
+
+            if pending.contains_key(&key) {
+                return;
+            }
+            pending.insert(key.clone(), None);
+        }
+
+        let (router, lookups) = (self.router.clone(), self.lookups.clone());
+        tokio::spawn(async move {
+            let _permit = lookups.permits.acquire().await.unwrap();
+            let res = router.route(from, to, mode).await;
+
+            let mut pending = lookups.pending.lock().unwrap();
+            match res {
+                Ok(_) => {
+                    pending.remove(&key);
+                }
+                Err(e) => {
+                    warn!("routing failed: {e}");
+                    pending.insert(key, Some(Instant::now()));
+                }
+            }
+        });
+    }
+
+    /// The way from `from` to every point of interest.
+    pub async fn from(&self, from: Place) -> Vec<Commute> {
+        join_all(self.pois.iter().map(|poi| async move {
+            Commute {
+                to: poi.name.clone(),
+                straight_line_m: haversine(from, poi.place),
+                walking: self.route(from, poi.place, Mode::Walking).await,
+                cycling: self.route(from, poi.place, Mode::Cycling).await,
+            }
+        }))
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    use axum::async_trait;
+
+    use super::{
+        cache_key, haversine, Commutes, Estimate, Mode, PointOfInterest, Route, RouteError,
+        RouteProvider,
+    };
+    use crate::{db::DbError, geocode::Place};
+
+    /// Knows every route after being asked for it, unless it is broken.
+    #[derive(Default)]
+    struct Stub {
+        known: Mutex<HashMap<String, Option<Route>>>,
+        lookups: AtomicUsize,
+        broken: bool,
+    }
+
+    const ROUTE: Route = Route {
+        distance_m: 1.0,
+        duration_s: 2.0,
+    };
+
+    #[async_trait]
+    impl RouteProvider for Stub {
+        async fn route(
+            &self,
+            from: Place,
+            to: Place,
+            mode: Mode,
+        ) -> Result<Option<Route>, RouteError> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+
+            if self.broken {
+                // any error will do
+                let e = serde_json::from_str::<()>("").unwrap_err();
+                return Err(DbError::from(e).into());
+            }
+            self.known
+                .lock()
+                .unwrap()
+                .insert(cache_key(from, to, mode), Some(ROUTE));
+
+            Ok(Some(ROUTE))
+        }
+
+        async fn cached(
+            &self,
+            from: Place,
+            to: Place,
+            mode: Mode,
+        ) -> Result<Option<Option<Route>>, RouteError> {
+            Ok(self
+                .known
+                .lock()
+                .unwrap()
+                .get(&cache_key(from, to, mode))
+                .copied())
+        }
+    }
+
+    async fn settle(stub: &Stub, lookups: usize) {
+        for _ in 0..100 {
+            if stub.lookups.load(Ordering::SeqCst) >= lookups {
+                // let the lookups finish
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("routes were never looked up");
+    }
+
+    #[tokio::test]
+    async fn estimates_until_looked_up() {
+        let stub = Arc::new(Stub::default());
+        let lth: PointOfInterest = "LTH=55.7110,13.2100".parse().unwrap();
+        let commutes = Commutes::new(vec![lth.clone()], stub.clone());
+        let from = Place {
+            lat: 55.7056,
+            lon: 13.1868,
+        };
+
+        let estimated = commutes.from(from).await;
+        let walking = Estimate::estimate(from, lth.place, Mode::Walking);
+        assert_eq!(estimated[0].walking.unwrap().duration_s, walking.duration_s);
+
+        settle(&stub, 2).await;
+        let looked_up = commutes.from(from).await;
+        assert_eq!(looked_up[0].walking.unwrap().duration_s, ROUTE.duration_s);
+        assert_eq!(looked_up[0].cycling.unwrap().duration_s, ROUTE.duration_s);
+        assert_eq!(stub.lookups.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn failures_are_not_retried_at_once() {
+        let stub = Arc::new(Stub {
+            broken: true,
+            ..Default::default()
+        });
+        let lth: PointOfInterest = "LTH=55.7110,13.2100".parse().unwrap();
+        let commutes = Commutes::new(vec![lth], stub.clone());
+        let from = Place {
+            lat: 55.7056,
+            lon: 13.1868,
+        };
+
+        commutes.from(from).await;
+        settle(&stub, 2).await;
+        let commutes = commutes.from(from).await;
+        assert!(commutes[0].walking.is_some());
+        settle(&stub, 2).await;
+        assert_eq!(stub.lookups.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn lund_c_to_lth() {
+        let lund_c: PointOfInterest = "Lund C=55.7056,13.1868".parse().unwrap();
+        let lth: PointOfInterest = "LTH = 55.7110, 13.2100".parse().unwrap();
+
+        assert_eq!(lth.name, "LTH");
+        let d = haversine(lund_c.place, lth.place);
+        assert!((1500.0..1600.0).contains(&d), "{d}");
+    }
+}
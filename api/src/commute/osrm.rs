@@ -0,0 +1,95 @@
+use axum::async_trait;
+use reqwest::Url;
+use serde::Deserialize;
+use tracing::debug;
+
+use super::{cache_key, Mode, Route, RouteError, RouteProvider};
+use crate::{
+    db::{Db, ROUTES},
+    geocode::{base_url, Place},
+};
+
+/// Routes with an [OSRM](https://project-osrm.org/) compatible API. As
+/// an OSRM server only serves the profile it was started with, walking and
+/// cycling have separate base URLs (which may well be the same). Routes
+/// are cached in the database.
+pub struct Osrm {
+    client: reqwest::Client,
+    db: Db,
+    walking: Url,
+    cycling: Url,
+}
+
+impl Osrm {
+    pub fn new(client: reqwest::Client, db: Db, walking: Url, cycling: Url) -> Self {
+        Self {
+            client,
+            db,
+            walking: base_url(walking),
+            cycling: base_url(cycling),
+        }
+    }
+}
+
+#[async_trait]
+impl RouteProvider for Osrm {
+    async fn route(&self, from: Place, to: Place, mode: Mode) -> Result<Option<Route>, RouteError> {
+        #[derive(Deserialize)]
+        struct OsrmRoute {
+            distance: f64,
+            duration: f64,
+        }
+
+        #[derive(Deserialize)]
+        struct Response {
+            #[serde(default)]
+            routes: Vec<OsrmRoute>,
+        }
+
+        let key = cache_key(from, to, mode);
+
+        if let Some(route) = self.db.get(ROUTES, &key).await? {
+            return Ok(route);
+        }
+
+        let (base, profile) = match mode {
+            Mode::Walking => (&self.walking, "foot"),
+            Mode::Cycling => (&self.cycling, "bike"),
+        };
+        let url = base
+            .join(&format!(
+                "route/v1/{profile}/{},{};{},{}",
+                from.lon, from.lat, to.lon, to.lat
+            ))
+            .unwrap();
+
+        debug!("routing {url}");
+
+        let res = self
+            .client
+            .get(url)
+            .query(&[("overview", "false")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Response>()
+            .await?;
+
+        let route = res.routes.into_iter().next().map(|r| Route {
+            distance_m: r.distance,
+            duration_s: r.duration,
+        });
+        self.db.insert(ROUTES, &key, &route).await?;
+
+        Ok(route)
+    }
+
+    async fn cached(
+        &self,
+        from: Place,
+        to: Place,
+        mode: Mode,
+    ) -> Result<Option<Option<Route>>, RouteError> {
+        Ok(self.db.get(ROUTES, &cache_key(from, to, mode)).await?)
+    }
+}
@@ -0,0 +1,582 @@
+//! Settings, from command-line flags, environment variables and an optional
+//! TOML file, in that order of precedence.
+//!
+//! The file uses the same names as the flags (with dashes or underscores).
+//! Flags that can be given several times also go by their plural:
+//!
+//! ```toml
+//! bind = "[::]:8080"
+//! cors-origin = ["https://bostader.amcoff.net"]
+//! geocoder = "photon"
+//! pois = ["LTH=55.7110,13.2100"]
+//! ```
+
+use std::{
+    ffi::OsString,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use afbostader::{Fingerprint, ThrottleConfig};
+use axum::http::{HeaderName, HeaderValue};
+use axum_extra::extract::cookie::{Cookie, SameSite};
+use clap::{ArgAction, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use reqwest::Url;
+
+use crate::{commute::PointOfInterest, login::LoginLimits, telemetry::LogFormat};
+
+/// Nominatim's usage policy allows at most one request per second.
+const PUBLIC_NOMINATIM: &str = "nominatim.openstreetmap.org";
+
+/// Photon's public instance asks for fair use.
+const PUBLIC_PHOTON: &str = "photon.komoot.io";
+
+/// Too short keys make [`Key::from`](axum_extra::extract::cookie::Key::from)
+/// panic.
+const MIN_COOKIE_KEY_LEN: usize = 64;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("failed to read {0}: {1}")]
+    Read(PathBuf, #[source] std::io::Error),
+    #[error("failed to parse {0}: {1}")]
+    Toml(PathBuf, #[source] toml::de::Error),
+    #[error("unknown setting `{0}` in config file")]
+    UnknownKey(String),
+    #[error("setting `{0}` in config file must be a string, number, boolean or array")]
+    UnsupportedValue(String),
+    #[error(transparent)]
+    Args(#[from] clap::Error),
+    #[error("{0}")]
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum GeocoderKind {
+    Nominatim,
+    Photon,
+    /// Look up addresses in a local CSV file.
+    Gazetteer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CookieSameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl From<CookieSameSite> for SameSite {
+    fn from(value: CookieSameSite) -> Self {
+        match value {
+            CookieSameSite::Strict => Self::Strict,
+            CookieSameSite::Lax => Self::Lax,
+            CookieSameSite::None => Self::None,
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Config {
+    /// TOML file with default values for any of these settings.
+    #[clap(long, env)]
+    pub config: Option<PathBuf>,
+    /// Address to listen on.
+    #[clap(long, env, default_value = "[::]:8000")]
+    pub bind: SocketAddr,
+    /// Port to listen on, overriding the port of `--bind`.
+    #[clap(long, env)]
+    pub port: Option<u16>,
+    /// Address to serve Prometheus metrics on, which should not be
+    /// reachable from the internet.
+    #[clap(long, env, default_value = "127.0.0.1:9091")]
+    pub metrics_bind: SocketAddr,
+    /// Allow settings that only make sense when developing locally, such
+    /// as a frontend on localhost. On by default in debug builds.
+    #[clap(long, env, default_value_t = cfg!(debug_assertions), action = ArgAction::Set)]
+    pub dev: bool,
+    /// Origin of a frontend (e.g. `https://example.com`) allowed to make
+    /// credentialed and state-changing requests. May be repeated. Must be
+    /// set to something other than localhost unless `--dev` is on.
+    #[clap(
+        long = "cors-origin",
+        env = "CORS_ORIGINS",
+        value_delimiter = ',',
+        default_values = ["http://localhost:3000"],
+    )]
+    pub cors_origins: Vec<String>,
+    /// Where the frontend is served, for links to it in calendars. Must be
+    /// set to something other than localhost unless `--dev` is on.
+    #[clap(long, env, default_value = "http://localhost:3000/")]
+    pub frontend_url: Url,
+    /// At least 64 bytes used to encrypt cookies. A random key is generated
+    /// if unset, which logs everybody out on restart.
+    #[clap(long, env)]
+    pub cookie_key: Option<String>,
+    /// Only send cookies over HTTPS.
+    #[clap(long, env, default_value_t = true, action = ArgAction::Set)]
+    pub cookie_secure: bool,
+    #[clap(long, env, value_enum, default_value_t = CookieSameSite::None)]
+    pub cookie_same_site: CookieSameSite,
+    /// Domain to set cookies for, e.g. to share them with subdomains.
+    #[clap(long, env)]
+    pub cookie_domain: Option<String>,
+    /// Number of threads rendering floorplans. pdfium can only be used by
+    /// one thread, so this is ignored for it.
+    #[clap(long, env, default_value_t = 2)]
+    pub render_workers: usize,
+    /// Number of floorplans waiting to be rendered before new requests are
+    /// rejected.
+    #[clap(long, env, default_value_t = 16)]
+    pub render_queue_size: usize,
+    /// Seconds to wait for a floorplan to be rendered. The render itself
+    /// can't be interrupted and keeps its thread busy until it is done.
+    #[clap(long, env, default_value_t = 30)]
+    pub render_timeout: u64,
+    /// Where to store data that should survive restarts, such as geocoding
+    /// results and resized pictures.
+    #[clap(long, env, default_value = "bostader.redb")]
+    pub db_path: PathBuf,
+    /// The geocoding service to use.
+    #[clap(long, env, value_enum, default_value_t = GeocoderKind::Nominatim)]
+    pub geocoder: GeocoderKind,
+    #[clap(long, env, default_value = "https://nominatim.openstreetmap.org/")]
+    pub nominatim_url: Url,
+    #[clap(long, env, default_value = "https://photon.komoot.io/")]
+    pub photon_url: Url,
+    /// CSV file with the columns `street,postal_code,city,lat,lon`.
+    #[clap(long, env)]
+    pub gazetteer_path: Option<PathBuf>,
+    /// Maximum number of requests per second to the geocoding service.
+    #[clap(long, env, default_value_t = 1.0)]
+    pub geocode_rate: f64,
+    /// Minutes between geocoding the addresses of new vacancies.
+    #[clap(long, env, default_value_t = 30)]
+    pub geocode_prewarm_interval: u64,
+    /// A place to show the commute to, as `NAME=LAT,LON`. May be repeated.
+    #[clap(
+        long = "poi",
+        env = "POIS",
+        value_delimiter = ';',
+        default_values = ["LTH=55.7110,13.2100", "Lund C=55.7056,13.1868", "Universitetshuset=55.7060,13.1940"],
+    )]
+    pub pois: Vec<PointOfInterest>,
+    /// OSRM server for walking directions. Travel times are estimated
+    /// from the straight-line distance if unset, and until a route has
+    /// been looked up, which happens in the background.
+    #[clap(long, env)]
+    pub osrm_walking_url: Option<Url>,
+    /// OSRM server for cycling directions.
+    #[clap(long, env)]
+    pub osrm_cycling_url: Option<Url>,
+    /// SHA-256 fingerprint of a certificate that AF must present, as
+    /// printed by `openssl x509 -fingerprint -sha256`. May be repeated.
+    #[clap(long = "af-cert-pin", env = "AF_CERT_PINS", value_delimiter = ',')]
+    pub af_cert_pins: Vec<Fingerprint>,
+    /// PEM file with intermediate certificates to complete AF's chain with,
+    /// in addition to the bundled ones.
+    #[clap(long, env)]
+    pub af_intermediates: Option<PathBuf>,
+    /// Requests per second to AF, shared by all users.
+    #[clap(long, env, default_value_t = 5.0)]
+    pub af_rate: f64,
+    /// Requests that may be sent to AF at once after a quiet period.
+    #[clap(long, env, default_value_t = 10)]
+    pub af_burst: u32,
+    /// Consecutive failed requests to AF after which no more are sent for
+    /// `af-cooldown` seconds.
+    #[clap(long, env, default_value_t = 5)]
+    pub af_failure_threshold: u32,
+    #[clap(long, env, default_value_t = 30)]
+    pub af_cooldown: u64,
+    /// Login attempts per client IP address and login window.
+    #[clap(long, env, default_value_t = 20)]
+    pub login_ip_limit: usize,
+    /// Login attempts per email address and login window.
+    #[clap(long, env, default_value_t = 5)]
+    pub login_email_limit: usize,
+    /// Minutes over which login attempts are counted.
+    #[clap(long, env, default_value_t = 15)]
+    pub login_window: u64,
+    /// Header in which a trusted reverse proxy passes the client's IP
+    /// address, e.g. `Fly-Client-IP`. The peer address is used if unset.
+    #[clap(long, env)]
+    pub client_ip_header: Option<HeaderName>,
+    #[clap(long, env, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+    /// OTLP/HTTP collector to export traces to, e.g.
+    /// `http://localhost:4318/`.
+    #[clap(long, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<Url>,
+}
+
+/// Find `--config` before the arguments are parsed for real, since the
+/// file provides the defaults for the other arguments.
+fn config_path(args: &[OsString]) -> Option<PathBuf> {
+    let mut args = args.iter().skip(1);
+
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        } else if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(path.into());
+        }
+    }
+
+    std::env::var_os("CONFIG").map(PathBuf::from)
+}
+
+fn toml_values(key: &str, value: toml::Value) -> Result<Vec<String>, ConfigError> {
+    match value {
+        toml::Value::String(s) => Ok(vec![s]),
+        toml::Value::Integer(i) => Ok(vec![i.to_string()]),
+        toml::Value::Float(f) => Ok(vec![f.to_string()]),
+        toml::Value::Boolean(b) => Ok(vec![b.to_string()]),
+        toml::Value::Array(values) => values
+            .into_iter()
+            .map(|v| match v {
+                toml::Value::Array(_) | toml::Value::Table(_) => {
+                    Err(ConfigError::UnsupportedValue(key.to_owned()))
+                }
+                v => toml_values(key, v).map(|mut v| v.remove(0)),
+            })
+            .collect(),
+        toml::Value::Datetime(_) | toml::Value::Table(_) => {
+            Err(ConfigError::UnsupportedValue(key.to_owned()))
+        }
+    }
+}
+
+/// An origin is a URL with nothing but a scheme, host and port.
+fn parse_origin(origin: &str) -> Result<HeaderValue, ConfigError> {
+    let invalid = || ConfigError::Invalid(format!("`{origin}` is not an origin"));
+    let url = Url::parse(origin).map_err(|_| invalid())?;
+
+    if !matches!(url.scheme(), "http" | "https")
+        || url.host().is_none()
+        || url.path() != "/"
+        || url.query().is_some()
+        || url.fragment().is_some()
+    {
+        return Err(invalid());
+    }
+
+    HeaderValue::from_str(origin.trim_end_matches('/')).map_err(|_| invalid())
+}
+
+impl Config {
+    /// Read the settings of this process, exiting on invalid arguments or
+    /// `--help`.
+    pub fn load() -> Result<Self, ConfigError> {
+        match Self::load_from(std::env::args_os()) {
+            Err(ConfigError::Args(e)) => e.exit(),
+            res => res,
+        }
+    }
+
+    pub fn load_from<I, T>(args: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        let mut cmd = Self::command();
+
+        if let Some(path) = config_path(&args) {
+            let file = read_file(&path)?;
+
+            for (key, value) in file {
+                let name = key.replace('-', "_");
+                let Some(id) = cmd
+                    .get_arguments()
+                    .find(|a| {
+                        a.get_id() == name.as_str()
+                            || a.get_long().is_some_and(|l| l.replace('-', "_") == name)
+                    })
+                    .map(|a| a.get_id().clone())
+                    .filter(|id| id != "config")
+                else {
+                    return Err(ConfigError::UnknownKey(key));
+                };
+
+                let values = toml_values(&key, value)?;
+                cmd = cmd.mut_arg(id, |arg| arg.default_values(values));
+            }
+        }
+
+        let config = Self::from_arg_matches(&cmd.try_get_matches_from(args)?)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |msg: &str| Err(ConfigError::Invalid(msg.to_owned()));
+
+        if self
+            .cookie_key
+            .as_ref()
+            .is_some_and(|key| key.len() < MIN_COOKIE_KEY_LEN)
+        {
+            return invalid("cookie-key must be at least 64 bytes long");
+        }
+        if self.cookie_same_site == CookieSameSite::None && !self.cookie_secure {
+            return invalid("cookie-same-site=none requires cookie-secure=true");
+        }
+        if self.render_workers == 0 || self.render_queue_size == 0 || self.render_timeout == 0 {
+            return invalid(
+                "render-workers, render-queue-size and render-timeout must be positive",
+            );
+        }
+        if !(self.geocode_rate.is_finite() && self.geocode_rate > 0.0) {
+            return invalid("geocode-rate must be positive");
+        }
+        if matches!(self.geocoder, GeocoderKind::Nominatim)
+            && self.nominatim_url.host_str() == Some(PUBLIC_NOMINATIM)
+            && self.geocode_rate > 1.0
+        {
+            return invalid("the public Nominatim instance allows at most 1 request per second");
+        }
+        if matches!(self.geocoder, GeocoderKind::Photon)
+            && self.photon_url.host_str() == Some(PUBLIC_PHOTON)
+            && self.geocode_rate > 1.0
+        {
+            return invalid("the public Photon instance allows at most 1 request per second");
+        }
+        if matches!(self.geocoder, GeocoderKind::Gazetteer) && self.gazetteer_path.is_none() {
+            return invalid("geocoder=gazetteer requires gazetteer-path");
+        }
+        if self.geocode_prewarm_interval == 0 {
+            return invalid("geocode-prewarm-interval must be positive");
+        }
+        if self.osrm_walking_url.is_some() != self.osrm_cycling_url.is_some() {
+            return invalid("osrm-walking-url and osrm-cycling-url must be set together");
+        }
+        if !(self.af_rate.is_finite() && self.af_rate > 0.0) {
+            return invalid("af-rate must be positive");
+        }
+        if self.af_burst == 0 || self.af_failure_threshold == 0 || self.af_cooldown == 0 {
+            return invalid("af-burst, af-failure-threshold and af-cooldown must be positive");
+        }
+        if self.login_ip_limit == 0 || self.login_email_limit == 0 || self.login_window == 0 {
+            return invalid("login-ip-limit, login-email-limit and login-window must be positive");
+        }
+        self.cors_origins()?;
+        if !self.dev && self.cors_origins.iter().all(|o| is_localhost(o)) {
+            return invalid("cors-origins must include the frontend's origin outside of dev");
+        }
+        if !self.dev && is_localhost(self.frontend_url.as_str()) {
+            return invalid("frontend-url must be set outside of dev");
+        }
+
+        Ok(())
+    }
+
+    /// The address to listen on.
+    pub fn addr(&self) -> SocketAddr {
+        let mut addr = self.bind;
+
+        if let Some(port) = self.port {
+            addr.set_port(port);
+        }
+
+        addr
+    }
+
+    pub fn cors_origins(&self) -> Result<Vec<HeaderValue>, ConfigError> {
+        self.cors_origins.iter().map(|o| parse_origin(o)).collect()
+    }
+
+    pub fn cookies(&self) -> CookieSettings {
+        CookieSettings {
+            secure: self.cookie_secure,
+            same_site: self.cookie_same_site.into(),
+            domain: self.cookie_domain.clone(),
+        }
+    }
+
+    pub fn geocode_interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.geocode_rate)
+    }
+
+    pub fn geocode_prewarm_interval(&self) -> Duration {
+        Duration::from_secs(self.geocode_prewarm_interval * 60)
+    }
+
+    pub fn af_throttle(&self) -> ThrottleConfig {
+        ThrottleConfig {
+            rate: self.af_rate,
+            burst: self.af_burst,
+            failure_threshold: self.af_failure_threshold,
+            cooldown: Duration::from_secs(self.af_cooldown),
+        }
+    }
+
+    pub fn login_limits(&self) -> LoginLimits {
+        LoginLimits {
+            per_ip: self.login_ip_limit,
+            per_email: self.login_email_limit,
+            window: Duration::from_secs(self.login_window * 60),
+        }
+    }
+}
+
+fn is_localhost(url: &str) -> bool {
+    Url::parse(url)
+        .is_ok_and(|url| matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]")))
+}
+
+fn read_file(path: &Path) -> Result<toml::Table, ConfigError> {
+    let s = std::fs::read_to_string(path).map_err(|e| ConfigError::Read(path.into(), e))?;
+
+    toml::from_str(&s).map_err(|e| ConfigError::Toml(path.into(), e))
+}
+
+/// How cookies are set.
+#[derive(Debug, Clone)]
+pub struct CookieSettings {
+    pub secure: bool,
+    pub same_site: SameSite,
+    pub domain: Option<String>,
+}
+
+impl CookieSettings {
+    pub fn build(&self, name: &'static str, value: String) -> Cookie<'static> {
+        let mut cookie = Cookie::build((name, value))
+            .http_only(true)
+            .secure(self.secure)
+            .path("/")
+            .permanent()
+            .same_site(self.same_site);
+
+        if let Some(ref domain) = self.domain {
+            cookie = cookie.domain(domain.clone());
+        }
+
+        cookie.build()
+    }
+
+    /// A cookie that removes `name`, which must match the path and domain
+    /// that it was set with.
+    pub fn removal(&self, name: &'static str) -> Cookie<'static> {
+        let mut cookie = Cookie::build(name).path("/");
+
+        if let Some(ref domain) = self.domain {
+            cookie = cookie.domain(domain.clone());
+        }
+
+        cookie.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, ConfigError};
+
+    fn load(toml: &str, args: &[&str]) -> Result<Config, ConfigError> {
+        let path = std::env::temp_dir().join(format!(
+            "bostader-config-{}-{:?}.toml",
+            std::process::id(),
+            std::thread::current().id(),
+        ));
+        std::fs::write(&path, toml).unwrap();
+
+        let res = Config::load_from(
+            ["api", "--config", path.to_str().unwrap()]
+                .iter()
+                .chain(args),
+        );
+        std::fs::remove_file(path).unwrap();
+        res
+    }
+
+    #[test]
+    fn layers() {
+        let config = load(
+            r#"
+            render-workers = 4
+            render_queue_size = 8
+            bind = "127.0.0.1:3000"
+            pois = ["A=1,2", "B=3,4"]
+            cors-origins = ["https://example.com"]
+            "#,
+            &["--render-workers", "3"],
+        )
+        .unwrap();
+
+        // flags take precedence over the file
+        assert_eq!(config.render_workers, 3);
+        assert_eq!(config.render_queue_size, 8);
+        assert_eq!(config.addr().to_string(), "127.0.0.1:3000");
+        assert_eq!(config.pois.len(), 2);
+        assert_eq!(config.cors_origins().unwrap(), ["https://example.com"]);
+        assert!(config.cookie_secure);
+    }
+
+    #[test]
+    fn flag_names() {
+        let config = load(
+            r#"
+            poi = ["A=1,2"]
+            cors-origin = ["https://example.com"]
+            af_cert_pin = []
+            "#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(config.pois.len(), 1);
+        assert_eq!(config.cors_origins().unwrap(), ["https://example.com"]);
+        assert!(config.af_cert_pins.is_empty());
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(
+            load("no-such-setting = 1", &[]),
+            Err(ConfigError::UnknownKey(_))
+        ));
+        assert!(matches!(
+            load("render-workers = \"many\"", &[]),
+            Err(ConfigError::Args(_))
+        ));
+        assert!(matches!(
+            load("cookie-secure = false", &[]),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            load("cors-origins = [\"https://example.com/path\"]", &[]),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            load("geocoder = \"gazetteer\"", &[]),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn localhost_outside_dev() {
+        assert!(load("dev = true", &[]).is_ok());
+        assert!(matches!(
+            load("dev = false", &[]),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            load("dev = false\ncors-origins = [\"https://example.com\"]", &[]),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(load(
+            r#"
+            dev = false
+            cors-origins = ["http://localhost:3000", "https://example.com"]
+            frontend-url = "https://example.com/"
+            "#,
+            &[]
+        )
+        .is_ok());
+    }
+}
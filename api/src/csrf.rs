@@ -0,0 +1,110 @@
+//! Protection against cross-site request forgery.
+//!
+//! Browsers send an `Origin` header with every cross-origin request, and
+//! with same-origin requests that are not `GET` or `HEAD`. State-changing
+//! requests from origins other than the allowed ones are rejected, so that
+//! a third-party page cannot log a visitor in or out (or anything else)
+//! using their cookies.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tracing::warn;
+
+/// The origins of the frontends that may use the API.
+#[derive(Debug, Clone)]
+pub struct AllowedOrigins(Arc<[HeaderValue]>);
+
+impl AllowedOrigins {
+    pub fn new(origins: Vec<HeaderValue>) -> Self {
+        Self(origins.into())
+    }
+
+    pub fn contains(&self, origin: &HeaderValue) -> bool {
+        self.0.contains(origin)
+    }
+
+    pub fn to_vec(&self) -> Vec<HeaderValue> {
+        self.0.to_vec()
+    }
+}
+
+/// Middleware rejecting state-changing requests from foreign origins.
+///
+/// Requests without an `Origin` header are let through, as they do not
+/// come from a (modern) browser and thus carry no ambient credentials.
+pub async fn verify_origin(
+    State(origins): State<AllowedOrigins>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if req.method().is_safe() {
+        return next.run(req).await;
+    }
+
+    match req.headers().get(header::ORIGIN) {
+        Some(origin) if !origins.contains(origin) => {
+            warn!(
+                ?origin,
+                "rejected {} {} from foreign origin",
+                req.method(),
+                req.uri()
+            );
+            (StatusCode::FORBIDDEN, "cross-origin request rejected").into_response()
+        }
+        _ => next.run(req).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        middleware,
+        routing::post,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use super::{verify_origin, AllowedOrigins};
+
+    async fn status(method: &str, origin: Option<&str>) -> StatusCode {
+        let origins = AllowedOrigins::new(vec!["https://bostader.example".parse().unwrap()]);
+        let app = Router::new()
+            .route("/logout", post(|| async {}).get(|| async {}))
+            .layer(middleware::from_fn_with_state(origins, verify_origin));
+        let mut req = Request::builder().method(method).uri("/logout");
+        if let Some(origin) = origin {
+            req = req.header("origin", origin);
+        }
+
+        app.oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn origins() {
+        assert_eq!(
+            status("POST", Some("https://bostader.example")).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status("POST", Some("https://evil.example")).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(status("POST", Some("null")).await, StatusCode::FORBIDDEN);
+        assert_eq!(status("POST", None).await, StatusCode::OK);
+        assert_eq!(
+            status("GET", Some("https://evil.example")).await,
+            StatusCode::OK
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use std::{path::Path, sync::Arc};
+
+use redb::{
+    backends::InMemoryBackend, Database, ReadableDatabase, ReadableTable, ReadableTableMetadata,
+    TableDefinition, TableError,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Values are stored as JSON (or raw bytes), keyed by strings.
+pub type Table = TableDefinition<'static, &'static str, &'static [u8]>;
+
+/// Geocoding results by geocoder and normalized address.
+pub const GEOCODE: Table = TableDefinition::new("geocode");
+
+/// Routes between coordinates, by mode of transport.
+pub const ROUTES: Table = TableDefinition::new("routes");
+
+/// Written to by readiness checks.
+pub const HEALTH: Table = TableDefinition::new("health");
+
+/// Filters saved for calendar feeds, by secret token.
+pub const CALENDARS: Table = TableDefinition::new("calendars");
+
+/// When each vacancy was first seen, as a Unix timestamp, by property ID.
+pub const FIRST_SEEN: Table = TableDefinition::new("first_seen");
+
+/// Resized area pictures, by size and original URL.
+pub const PICTURES: Table = TableDefinition::new("pictures");
+
+#[derive(Debug, thiserror::Error)]
+pub enum DbError {
+    #[error(transparent)]
+    Redb(#[from] redb::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// A small key-value store for data that should survive restarts.
+#[derive(Clone)]
+pub struct Db {
+    db: Arc<Database>,
+}
+
+impl Db {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, DbError> {
+        let db = Database::create(path).map_err(redb::Error::from)?;
+
+        Ok(Self { db: Arc::new(db) })
+    }
+
+    /// A database that is lost when the process exits.
+    pub fn in_memory() -> Result<Self, DbError> {
+        let db = Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .map_err(redb::Error::from)?;
+
+        Ok(Self { db: Arc::new(db) })
+    }
+
+    pub async fn get<T>(&self, table: Table, key: &str) -> Result<Option<T>, DbError>
+    where
+        T: DeserializeOwned,
+    {
+        match self.get_bytes(table, key).await? {
+            Some(v) => Ok(Some(serde_json::from_slice(&v)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn insert<T>(&self, table: Table, key: &str, value: &T) -> Result<(), DbError>
+    where
+        T: Serialize,
+    {
+        self.insert_bytes(table, key, serde_json::to_vec(value)?)
+            .await
+    }
+
+    pub async fn get_bytes(&self, table: Table, key: &str) -> Result<Option<Vec<u8>>, DbError> {
+        let db = self.db.clone();
+        let key = key.to_owned();
+
+        tokio::task::spawn_blocking(move || {
+            let tx = db.begin_read().map_err(redb::Error::from)?;
+            let table = match tx.open_table(table) {
+                Ok(table) => table,
+                // nothing has been written yet
+                Err(TableError::TableDoesNotExist(_)) => return Ok(None),
+                Err(e) => return Err(redb::Error::from(e).into()),
+            };
+
+            Ok(table
+                .get(key.as_str())
+                .map_err(redb::Error::from)?
+                .map(|v| v.value().to_vec()))
+        })
+        .await
+        .unwrap()
+    }
+
+    pub async fn insert_bytes(
+        &self,
+        table: Table,
+        key: &str,
+        value: Vec<u8>,
+    ) -> Result<(), DbError> {
+        let db = self.db.clone();
+        let key = key.to_owned();
+
+        tokio::task::spawn_blocking(move || {
+            let tx = db.begin_write().map_err(redb::Error::from)?;
+            tx.open_table(table)
+                .map_err(redb::Error::from)?
+                .insert(key.as_str(), value.as_slice())
+                .map_err(redb::Error::from)?;
+            tx.commit().map_err(redb::Error::from)?;
+
+            Ok(())
+        })
+        .await
+        .unwrap()
+    }
+
+    /// The number of entries in `table`.
+    pub async fn len(&self, table: Table) -> Result<u64, DbError> {
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let tx = db.begin_read().map_err(redb::Error::from)?;
+
+            match tx.open_table(table) {
+                Ok(table) => Ok(table.len().map_err(redb::Error::from)?),
+                Err(TableError::TableDoesNotExist(_)) => Ok(0),
+                Err(e) => Err(redb::Error::from(e).into()),
+            }
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Remove the entry of `table` for which `key` is smallest, returning
+    /// whether there was one. Entries that can't be deserialized are
+    /// skipped, and left for [`Db::retain`] to remove.
+    pub async fn remove_min_by_key<T, K, F>(&self, table: Table, key: F) -> Result<bool, DbError>
+    where
+        T: DeserializeOwned,
+        K: Ord,
+        F: Fn(&T) -> K + Send + 'static,
+    {
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let tx = db.begin_write().map_err(redb::Error::from)?;
+            let removed = {
+                let mut table = tx.open_table(table).map_err(redb::Error::from)?;
+                let mut min: Option<(K, String)> = None;
+                for entry in table.iter().map_err(redb::Error::from)? {
+                    let (k, v) = entry.map_err(redb::Error::from)?;
+                    let Ok(v) = serde_json::from_slice(v.value()) else {
+                        continue;
+                    };
+                    let v = key(&v);
+                    if min.as_ref().is_none_or(|(min, _)| v < *min) {
+                        min = Some((v, k.value().to_owned()));
+                    }
+                }
+
+                match min {
+                    Some((_, k)) => {
+                        table.remove(k.as_str()).map_err(redb::Error::from)?;
+                        true
+                    }
+                    None => false,
+                }
+            };
+            tx.commit().map_err(redb::Error::from)?;
+
+            Ok(removed)
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Remove the entries of `table` for which `keep` returns false, as
+    /// well as any that can't be deserialized, returning how many were
+    /// removed.
+    pub async fn retain<T, F>(&self, table: Table, keep: F) -> Result<u64, DbError>
+    where
+        T: DeserializeOwned,
+        F: Fn(&str, T) -> bool + Send + 'static,
+    {
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let tx = db.begin_write().map_err(redb::Error::from)?;
+            let removed = {
+                let mut table = tx.open_table(table).map_err(redb::Error::from)?;
+                let before = table.len().map_err(redb::Error::from)?;
+                table
+                    .retain(|k, v| serde_json::from_slice(v).is_ok_and(|v| keep(k, v)))
+                    .map_err(redb::Error::from)?;
+
+                before - table.len().map_err(redb::Error::from)?
+            };
+            tx.commit().map_err(redb::Error::from)?;
+
+            Ok(removed)
+        })
+        .await
+        .unwrap()
+    }
+}
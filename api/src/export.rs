@@ -0,0 +1,311 @@
+//! Vacancy lists as spreadsheets, for comparing them side by side.
+
+use afbostader::{Property, PropertyDetail};
+use reqwest::Url;
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook, XlsxError};
+use time::Date;
+
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Detailed exports that one IP address can make per hour, as each takes
+/// a request to AF per vacancy.
+pub const DETAILED_EXPORTS_PER_HOUR: usize = 10;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+    #[error(transparent)]
+    Xlsx(#[from] XlsxError),
+}
+
+/// A vacancy, with its details if they were asked for.
+pub struct Row<'a> {
+    pub property: &'a Property,
+    pub detail: Option<&'a PropertyDetail>,
+}
+
+enum Cell {
+    Text(String),
+    Number(f64),
+    Date(Date),
+    Empty,
+}
+
+impl From<String> for Cell {
+    fn from(s: String) -> Self {
+        Self::Text(s)
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(s: &str) -> Self {
+        Self::Text(s.to_owned())
+    }
+}
+
+impl<T: Into<f64>> From<Option<T>> for Cell {
+    fn from(n: Option<T>) -> Self {
+        n.map_or(Self::Empty, |n| Self::Number(n.into()))
+    }
+}
+
+const COLUMNS: [&str; 19] = [
+    "ID",
+    "Adress",
+    "Postnummer",
+    "Ort",
+    "Område",
+    "Typ",
+    "Beskrivning",
+    "Våning",
+    "Storlek (m²)",
+    "Hyra (kr/mån)",
+    "Hyra per m²",
+    "Köplats",
+    "I kö",
+    "Anmälan från",
+    "Anmälan till",
+    "Dagar kvar",
+    "Inflyttning",
+    "Prioritet",
+    "Länk",
+];
+
+const DETAIL_COLUMNS: [&str; 12] = [
+    "Status",
+    "Förråd",
+    "Dusch",
+    "Möblering",
+    "Balkong",
+    "Kök",
+    "Hiss",
+    "Värme",
+    "El",
+    "Internet",
+    "Väderstreck",
+    "Planritning",
+];
+
+fn columns(detail: bool) -> impl Iterator<Item = &'static str> {
+    COLUMNS
+        .into_iter()
+        .chain(DETAIL_COLUMNS.into_iter().filter(move |_| detail))
+}
+
+/// Monthly rent per square metre, to two decimals.
+fn rent_per_sqm(p: &Property) -> Option<f64> {
+    (p.size_sqm > 0.0).then(|| (f64::from(p.rent) / f64::from(p.size_sqm) * 100.0).round() / 100.0)
+}
+
+/// Days left to register interest, which is negative once it's too late.
+fn days_until_deadline(p: &Property, today: Date) -> i64 {
+    (p.reserve_until - today).whole_days()
+}
+
+fn cells(row: &Row, detail: bool, frontend: &Url, today: Date) -> Vec<Cell> {
+    let p = row.property;
+    let mut cells: Vec<Cell> = vec![
+        Some(p.id).into(),
+        p.address.street.as_str().into(),
+        p.address.postal_code.as_str().into(),
+        p.address.city.as_str().into(),
+        p.area.as_str().into(),
+        match &p.property_type {
+            afbostader::PropertyType::Apartment => "Lägenhet",
+            afbostader::PropertyType::Dorm => "Korridorrum",
+            afbostader::PropertyType::Other(s) => s.as_str(),
+        }
+        .into(),
+        p.short_description.as_str().into(),
+        Some(p.floor).into(),
+        Some(p.size_sqm).into(),
+        Some(p.rent).into(),
+        rent_per_sqm(p).into(),
+        p.queue_position.position.into(),
+        Some(p.queue_position.total_in_queue).into(),
+        Cell::Date(p.reserve_from),
+        Cell::Date(p.reserve_until),
+        // whole days are exactly representable
+        Some(days_until_deadline(p, today) as f64).into(),
+        Cell::Date(p.move_in),
+        match &p.priority {
+            Some(afbostader::Priority::Novisch) => "Novisch",
+            Some(afbostader::Priority::Other(s)) => s.as_str(),
+            None => "",
+        }
+        .into(),
+        frontend
+            .join(&format!("bostad/{}", p.id))
+            .map_or(Cell::Empty, |url| url.to_string().into()),
+    ];
+
+    if detail {
+        match row.detail {
+            Some(d) => cells.extend([
+                d.status.as_str().into(),
+                d.store.included.as_str().into(),
+                d.shower.as_str().into(),
+                d.furniture.as_str().into(),
+                d.balcony.as_str().into(),
+                d.kitchen.as_str().into(),
+                d.elevator.as_str().into(),
+                d.heating.as_str().into(),
+                d.electricity.as_str().into(),
+                d.internet.as_str().into(),
+                d.facing.as_str().into(),
+                d.blueprint
+                    .as_ref()
+                    .map_or(Cell::Empty, |url| url.to_string().into()),
+            ]),
+            None => cells.extend(DETAIL_COLUMNS.map(|_| Cell::Empty)),
+        }
+    }
+
+    cells
+}
+
+/// Text that a spreadsheet would take for a formula, made to be read as
+/// text, since the listings are written by others than the user.
+fn defuse(s: String) -> String {
+    if s.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{s}")
+    } else {
+        s
+    }
+}
+
+/// A CSV file with a byte order mark, without which Excel assumes some
+/// other encoding than UTF-8.
+pub fn csv(
+    rows: &[Row],
+    detail: bool,
+    frontend: &Url,
+    today: Date,
+) -> Result<Vec<u8>, ExportError> {
+    let mut writer = csv::Writer::from_writer(Vec::from(BOM));
+    writer.write_record(columns(detail))?;
+
+    for row in rows {
+        writer.write_record(cells(row, detail, frontend, today).into_iter().map(
+            |cell| match cell {
+                Cell::Text(s) => defuse(s),
+                Cell::Number(n) => n.to_string(),
+                Cell::Date(d) => d.to_string(),
+                Cell::Empty => String::new(),
+            },
+        ))?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| ExportError::Csv(e.into_error().into()))
+}
+
+pub fn xlsx(
+    rows: &[Row],
+    detail: bool,
+    frontend: &Url,
+    today: Date,
+) -> Result<Vec<u8>, ExportError> {
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet().set_name("Lediga bostäder")?;
+    let bold = Format::new().set_bold();
+    let date = Format::new().set_num_format("yyyy-mm-dd");
+
+    for (col, name) in columns(detail).enumerate() {
+        sheet.write_string_with_format(0, col as u16, name, &bold)?;
+    }
+
+    for (i, row) in rows.iter().enumerate() {
+        let r = i as u32 + 1;
+
+        for (col, cell) in cells(row, detail, frontend, today).into_iter().enumerate() {
+            let col = col as u16;
+
+            match cell {
+                Cell::Text(s) => {
+                    sheet.write_string(r, col, s)?;
+                }
+                Cell::Number(n) => {
+                    sheet.write_number(r, col, n)?;
+                }
+                Cell::Date(d) => {
+                    let d = ExcelDateTime::from_ymd(d.year() as u16, d.month().into(), d.day())?;
+                    sheet.write_datetime_with_format(r, col, &d, &date)?;
+                }
+                Cell::Empty => {}
+            }
+        }
+    }
+
+    sheet.set_freeze_panes(1, 0)?;
+    sheet.autofit();
+
+    Ok(workbook.save_to_buffer()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use reqwest::Url;
+    use time::Duration;
+
+    use super::{
+        csv, days_until_deadline, defuse, rent_per_sqm, xlsx, Row, BOM, COLUMNS, DETAIL_COLUMNS,
+    };
+    use crate::testing;
+
+    #[test]
+    fn export() {
+        let property = testing::property();
+        let detail = testing::property_detail();
+        let frontend = Url::parse("https://bostader.example.com/").unwrap();
+        let today = property.reserve_until - Duration::days(3);
+
+        assert_eq!(days_until_deadline(&property, today), 3);
+        assert_eq!(
+            rent_per_sqm(&property),
+            Some((f64::from(property.rent) / f64::from(property.size_sqm) * 100.0).round() / 100.0)
+        );
+
+        let rows = [
+            Row {
+                property: &property,
+                detail: None,
+            },
+            Row {
+                property: &detail.property,
+                detail: Some(&detail),
+            },
+        ];
+
+        let plain = csv(&rows, false, &frontend, today).unwrap();
+        assert!(plain.starts_with(BOM));
+        let mut reader = ::csv::Reader::from_reader(Cursor::new(&plain[BOM.len()..]));
+        assert_eq!(reader.headers().unwrap().len(), COLUMNS.len());
+        let records: Vec<_> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(&records[0][15], "3");
+
+        let detailed = csv(&rows, true, &frontend, today).unwrap();
+        let mut reader = ::csv::Reader::from_reader(Cursor::new(&detailed[BOM.len()..]));
+        assert_eq!(
+            reader.headers().unwrap().len(),
+            COLUMNS.len() + DETAIL_COLUMNS.len()
+        );
+        let records: Vec<_> = reader.records().map(Result::unwrap).collect();
+        assert_eq!(&records[0][COLUMNS.len()], "");
+        assert_eq!(&records[1][COLUMNS.len()], detail.status);
+
+        assert_eq!(defuse("=HYPERLINK(\"x\")".to_owned()), "'=HYPERLINK(\"x\")");
+        assert_eq!(defuse("-".to_owned()), "'-");
+        assert_eq!(defuse("Lund".to_owned()), "Lund");
+
+        // a zip archive
+        assert!(xlsx(&rows, true, &frontend, today)
+            .unwrap()
+            .starts_with(b"PK"));
+    }
+}
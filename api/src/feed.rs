@@ -0,0 +1,330 @@
+//! Atom and RSS feeds of new vacancies, for following them in a feed
+//! reader.
+//!
+//! AF doesn't say when a vacancy was listed, so we remember when we first
+//! saw each one, checking regularly so that it's close to the truth.
+
+use std::collections::HashSet;
+
+use afbostader::{Property, PropertyId};
+use reqwest::Url;
+use time::{
+    format_description::well_known::{Rfc2822, Rfc3339},
+    Duration, OffsetDateTime,
+};
+
+use crate::db::{Db, DbError, FIRST_SEEN};
+
+const TITLE: &str = "AF Bostäder";
+
+/// When a vacancy was first seen is remembered for at least this long
+/// after it is no longer listed, in case it is only missing for a while.
+const RETENTION: Duration = Duration::days(30);
+
+const DESCRIPTION: &str = "Nya lediga bostäder hos AF Bostäder";
+
+/// A vacancy and when we first saw it.
+pub struct Entry<'a> {
+    pub property: &'a Property,
+    pub first_seen: OffsetDateTime,
+}
+
+/// When each vacancy was first seen, remembering `now` for those that
+/// haven't been seen before.
+pub async fn first_seen(
+    db: &Db,
+    vacancies: &[Property],
+    now: OffsetDateTime,
+) -> Result<Vec<OffsetDateTime>, DbError> {
+    let mut seen = Vec::with_capacity(vacancies.len());
+
+    for property in vacancies {
+        let key = property.id.to_string();
+        let timestamp = match db.get::<i64>(FIRST_SEEN, &key).await? {
+            Some(timestamp) => timestamp,
+            None => {
+                db.insert(FIRST_SEEN, &key, &now.unix_timestamp()).await?;
+                now.unix_timestamp()
+            }
+        };
+
+        seen.push(OffsetDateTime::from_unix_timestamp(timestamp).unwrap_or(now));
+    }
+
+    Ok(seen)
+}
+
+/// Forget when vacancies that are no longer listed were first seen, unless
+/// that was recently, returning how many were forgotten.
+pub async fn prune(db: &Db, vacancies: &[Property], now: OffsetDateTime) -> Result<u64, DbError> {
+    let listed: HashSet<_> = vacancies.iter().map(|p| p.id.to_string()).collect();
+
+    db.retain(FIRST_SEEN, move |key, timestamp: i64| {
+        listed.contains(key) || now.unix_timestamp() - timestamp < RETENTION.whole_seconds()
+    })
+    .await
+}
+
+/// Newest first.
+pub fn sort(entries: &mut [Entry]) {
+    entries.sort_by(|a, b| {
+        b.first_seen
+            .cmp(&a.first_seen)
+            .then(b.property.id.cmp(&a.property.id))
+    });
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn link(frontend: &Url, id: PropertyId) -> Url {
+    frontend
+        .join(&format!("bostad/{id}"))
+        .unwrap_or_else(|_| frontend.clone())
+}
+
+fn title(p: &Property) -> String {
+    format!("{}, {}", p.address.street, p.area)
+}
+
+fn summary(p: &Property) -> String {
+    format!(
+        "{}. {} kr/mån, {} m² i {}. Sista dag för intresseanmälan {}.",
+        p.short_description, p.rent, p.size_sqm, p.area, p.reserve_until
+    )
+}
+
+/// An Atom feed of `entries`, which should already be sorted.
+pub fn atom(entries: &[Entry], frontend: &Url, now: OffsetDateTime) -> String {
+    let updated = entries.iter().map(|e| e.first_seen).max().unwrap_or(now);
+    let mut out = String::new();
+
+    out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    out.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    out.push_str(&format!("  <title>{}</title>\n", escape(TITLE)));
+    out.push_str(&format!("  <subtitle>{}</subtitle>\n", escape(DESCRIPTION)));
+    out.push_str(&format!("  <id>{}</id>\n", escape(frontend.as_str())));
+    out.push_str(&format!(
+        "  <link href=\"{}\"/>\n",
+        escape(frontend.as_str())
+    ));
+    out.push_str(&format!("  <updated>{}</updated>\n", rfc3339(updated)));
+    out.push_str(&format!(
+        "  <author><name>{}</name></author>\n",
+        escape(TITLE)
+    ));
+
+    for Entry {
+        property: p,
+        first_seen,
+    } in entries
+    {
+        let url = escape(link(frontend, p.id).as_str());
+
+        out.push_str("  <entry>\n");
+        out.push_str(&format!("    <title>{}</title>\n", escape(&title(p))));
+        out.push_str(&format!("    <id>{url}</id>\n"));
+        out.push_str(&format!("    <link href=\"{url}\"/>\n"));
+        out.push_str(&format!(
+            "    <published>{}</published>\n",
+            rfc3339(*first_seen)
+        ));
+        out.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            rfc3339(*first_seen)
+        ));
+        out.push_str(&format!("    <summary>{}</summary>\n", escape(&summary(p))));
+        out.push_str("  </entry>\n");
+    }
+
+    out.push_str("</feed>\n");
+
+    out
+}
+
+/// An RSS 2.0 feed of `entries`, which should already be sorted.
+pub fn rss(entries: &[Entry], frontend: &Url, now: OffsetDateTime) -> String {
+    let updated = entries.iter().map(|e| e.first_seen).max().unwrap_or(now);
+    let mut out = String::new();
+
+    out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    out.push_str("<rss version=\"2.0\">\n");
+    out.push_str("  <channel>\n");
+    out.push_str(&format!("    <title>{}</title>\n", escape(TITLE)));
+    out.push_str(&format!("    <link>{}</link>\n", escape(frontend.as_str())));
+    out.push_str(&format!(
+        "    <description>{}</description>\n",
+        escape(DESCRIPTION)
+    ));
+    out.push_str("    <language>sv</language>\n");
+    out.push_str(&format!(
+        "    <lastBuildDate>{}</lastBuildDate>\n",
+        rfc2822(updated)
+    ));
+
+    for Entry {
+        property: p,
+        first_seen,
+    } in entries
+    {
+        let url = escape(link(frontend, p.id).as_str());
+
+        out.push_str("    <item>\n");
+        out.push_str(&format!("      <title>{}</title>\n", escape(&title(p))));
+        out.push_str(&format!("      <link>{url}</link>\n"));
+        out.push_str(&format!("      <guid isPermaLink=\"true\">{url}</guid>\n"));
+        out.push_str(&format!(
+            "      <pubDate>{}</pubDate>\n",
+            rfc2822(*first_seen)
+        ));
+        out.push_str(&format!(
+            "      <description>{}</description>\n",
+            escape(&summary(p))
+        ));
+        out.push_str("    </item>\n");
+    }
+
+    out.push_str("  </channel>\n");
+    out.push_str("</rss>\n");
+
+    out
+}
+
+fn rfc3339(t: OffsetDateTime) -> String {
+    t.format(&Rfc3339).unwrap_or_default()
+}
+
+fn rfc2822(t: OffsetDateTime) -> String {
+    t.format(&Rfc2822).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use afbostader::Property;
+    use reqwest::Url;
+    use time::{Duration, OffsetDateTime};
+
+    use super::{atom, escape, first_seen, prune, rss, sort, Entry, RETENTION};
+    use crate::{db::Db, testing};
+
+    fn property(id: u32) -> Property {
+        let mut property = testing::property();
+        property.id = id;
+        property
+    }
+
+    #[tokio::test]
+    async fn remembers_first_seen() {
+        let db = Db::in_memory().unwrap();
+        let now = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let later = now + Duration::hours(1);
+
+        assert_eq!(first_seen(&db, &[property(1)], now).await.unwrap(), [now]);
+        assert_eq!(
+            first_seen(&db, &[property(1), property(2)], later)
+                .await
+                .unwrap(),
+            [now, later]
+        );
+
+        // only the vacancy that is gone, and has been for a while, is
+        // forgotten
+        let much_later = now + RETENTION + Duration::days(1);
+        assert_eq!(prune(&db, &[property(1)], later).await.unwrap(), 0);
+        assert_eq!(prune(&db, &[property(1)], much_later).await.unwrap(), 1);
+        assert_eq!(
+            first_seen(&db, &[property(1), property(2)], much_later)
+                .await
+                .unwrap(),
+            [now, much_later]
+        );
+    }
+
+    /// What the feeds are sorted by is kept when the API restarts, so that
+    /// a deploy doesn't make every vacancy new again.
+    #[tokio::test]
+    async fn survives_reopening() {
+        let path = std::env::temp_dir().join(format!("bostader-feed-{}.redb", std::process::id()));
+        let now = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let later = now + Duration::hours(1);
+        let (old, new) = (property(1), property(2));
+
+        let db = Db::open(&path).unwrap();
+        first_seen(&db, std::slice::from_ref(&old), now)
+            .await
+            .unwrap();
+        drop(db);
+
+        let db = Db::open(&path).unwrap();
+        let vacancies = [old, new];
+        let seen = first_seen(&db, &vacancies, later).await.unwrap();
+        drop(db);
+        std::fs::remove_file(&path).unwrap();
+
+        let mut entries: Vec<_> = vacancies
+            .iter()
+            .zip(seen)
+            .map(|(property, first_seen)| Entry {
+                property,
+                first_seen,
+            })
+            .collect();
+        sort(&mut entries);
+        let order: Vec<_> = entries
+            .iter()
+            .map(|e| (e.property.id, e.first_seen))
+            .collect();
+        assert_eq!(order, [(2, later), (1, now)]);
+    }
+
+    #[test]
+    fn feeds() {
+        let frontend = Url::parse("https://bostader.example.com/").unwrap();
+        let now = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let (old, new) = (property(1), property(2));
+        let mut entries = [
+            Entry {
+                property: &old,
+                first_seen: now - Duration::days(1),
+            },
+            Entry {
+                property: &new,
+                first_seen: now,
+            },
+        ];
+        sort(&mut entries);
+
+        let atom = atom(&entries, &frontend, now);
+        assert!(atom.contains("<updated>2023-11-14T22:13:20Z</updated>"));
+        assert!(
+            atom.find("https://bostader.example.com/bostad/2").unwrap()
+                < atom.find("https://bostader.example.com/bostad/1").unwrap()
+        );
+
+        let rss = rss(&entries, &frontend, now);
+        assert!(rss.contains("<pubDate>Tue, 14 Nov 2023 22:13:20 +0000</pubDate>"));
+        assert_eq!(rss.matches("<item>").count(), 2);
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(
+            escape("<a href='x'>&\"</a>"),
+            "&lt;a href=&apos;x&apos;&gt;&amp;&quot;&lt;/a&gt;"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Which vacancies to list, shared by the routes that list them.
+
+use async_graphql::{Enum, InputObject};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::v1;
+
+/// The type of a property, without the name of other types, which are
+/// too varied to filter by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, Enum)]
+#[serde(rename_all = "snake_case")]
+pub enum PropertyKind {
+    Apartment,
+    Dorm,
+    Other,
+}
+
+impl From<&afbostader::PropertyType> for PropertyKind {
+    fn from(t: &afbostader::PropertyType) -> Self {
+        match t {
+            afbostader::PropertyType::Apartment => Self::Apartment,
+            afbostader::PropertyType::Dorm => Self::Dorm,
+            afbostader::PropertyType::Other(_) => Self::Other,
+        }
+    }
+}
+
+impl From<&v1::PropertyType> for PropertyKind {
+    fn from(t: &v1::PropertyType) -> Self {
+        match t {
+            v1::PropertyType::Apartment => Self::Apartment,
+            v1::PropertyType::Dorm => Self::Dorm,
+            v1::PropertyType::Other(_) => Self::Other,
+        }
+    }
+}
+
+/// Longer than the name of any area, the longest of which is Michael
+/// Hansens kollegium, so a longer `area` can't match anything.
+pub const MAX_AREA_LEN: usize = 40;
+
+/// Every condition that is set must hold.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams, ToSchema, InputObject)]
+#[into_params(parameter_in = Query)]
+pub struct VacancyFilter {
+    /// The name of the area, case insensitive.
+    pub area: Option<String>,
+    pub property_type: Option<PropertyKind>,
+    pub min_rent: Option<u32>,
+    pub max_rent: Option<u32>,
+    pub min_size_sqm: Option<f32>,
+    pub max_size_sqm: Option<f32>,
+    pub reserved: Option<bool>,
+}
+
+impl VacancyFilter {
+    pub fn matches(&self, p: &afbostader::Property) -> bool {
+        self.area
+            .as_ref()
+            .is_none_or(|area| p.area.trim().eq_ignore_ascii_case(area.trim()))
+            && self
+                .property_type
+                .is_none_or(|t| t == PropertyKind::from(&p.property_type))
+            && self.min_rent.is_none_or(|rent| p.rent >= rent)
+            && self.max_rent.is_none_or(|rent| p.rent <= rent)
+            && self.min_size_sqm.is_none_or(|size| p.size_sqm >= size)
+            && self.max_size_sqm.is_none_or(|size| p.size_sqm <= size)
+            && self.reserved.is_none_or(|reserved| p.reserved == reserved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PropertyKind, VacancyFilter};
+    use crate::testing;
+
+    #[test]
+    fn matches() {
+        let property = testing::property();
+
+        assert!(VacancyFilter::default().matches(&property));
+        assert!(VacancyFilter {
+            area: Some(format!(" {} ", property.area.to_uppercase())),
+            max_rent: Some(property.rent),
+            min_size_sqm: Some(property.size_sqm),
+            ..Default::default()
+        }
+        .matches(&property));
+        assert!(!VacancyFilter {
+            min_rent: Some(property.rent + 1),
+            ..Default::default()
+        }
+        .matches(&property));
+        assert!(!VacancyFilter {
+            area: Some("Somewhere else".to_owned()),
+            ..Default::default()
+        }
+        .matches(&property));
+        assert!(!VacancyFilter {
+            reserved: Some(!property.reserved),
+            ..Default::default()
+        }
+        .matches(&property));
+
+        let kind = PropertyKind::from(&property.property_type);
+        for other in [
+            PropertyKind::Apartment,
+            PropertyKind::Dorm,
+            PropertyKind::Other,
+        ] {
+            let filter = VacancyFilter {
+                property_type: Some(other),
+                ..Default::default()
+            };
+            assert_eq!(filter.matches(&property), other == kind);
+        }
+    }
+}
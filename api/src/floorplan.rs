@@ -1,24 +1,122 @@
-use image::{DynamicImage, RgbaImage};
-use pdfium_render::{
-    error::PdfiumError, page::PdfPageRenderRotation, pdfium::Pdfium, prelude::PdfRenderConfig,
-};
+use std::{io::Cursor, time::Instant};
 
-fn pdf_to_image(bytes: Vec<u8>) -> Result<Option<DynamicImage>, PdfiumError> {
-    let pdfium = Pdfium::default();
+use axum::body::Bytes;
+use image::{ImageFormat, RgbaImage};
+use metrics::histogram;
+use reqwest::header::CONTENT_TYPE;
+use tracing::{info, warn};
 
-    let document = pdfium.load_pdf_from_byte_vec(bytes, None)?;
+#[cfg(feature = "hayro")]
+mod hayro;
+#[cfg(feature = "pdfium")]
+mod pdfium;
+mod pool;
+mod rooms;
 
-    let render_config = PdfRenderConfig::new()
-        .set_target_width(2000)
-        .set_maximum_height(2000)
-        .rotate_if_landscape(PdfPageRenderRotation::Degrees90, true);
+#[cfg(feature = "hayro")]
+pub use self::hayro::HayroRasterizer;
+#[cfg(feature = "pdfium")]
+pub use self::pdfium::PdfiumRasterizer;
+pub use pool::{PoolError, RenderPool};
+pub use rooms::{find_rooms, Room, TextSpan};
 
-    let Some(page) = document.pages().iter().next() else {
-        return Ok(None);
-    };
-    let bitmap = page.render_with_config(&render_config)?;
+/// Floorplans larger than this (in bytes) are rejected.
+const MAX_DOWNLOAD_SIZE: usize = 20 * 1024 * 1024;
 
-    Ok(Some(bitmap.as_image()))
+/// The size (in pixels) of the longest side of a rasterized floorplan.
+#[cfg(any(feature = "pdfium", feature = "hayro"))]
+const TARGET_SIZE: u16 = 2000;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RasterizeError {
+    #[cfg(feature = "pdfium")]
+    #[error("pdfium error")]
+    Pdfium(#[from] pdfium_render::error::PdfiumError),
+    #[cfg(feature = "hayro")]
+    #[error("hayro error: {0:?}")]
+    Hayro(::hayro::hayro_syntax::LoadPdfError),
+}
+
+/// Something that can turn the first page of a PDF into pixels.
+///
+/// Landscape pages are expected to be rotated to portrait and scaled so
+/// that they fit within a 2000x2000 box.
+pub trait Rasterizer {
+    /// Render the first page of `pdf`, or return `None` if the document
+    /// has no pages.
+    fn rasterize(&self, pdf: &[u8]) -> Result<Option<RgbaImage>, RasterizeError>;
+
+    /// Extract the text on the first page of `pdf`, or return `None` if
+    /// the document has no pages.
+    fn text(&self, pdf: &[u8]) -> Result<Option<Vec<TextSpan>>, RasterizeError>;
+}
+
+/// The rasterizer implementations enabled at compile time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    #[cfg(feature = "pdfium")]
+    Pdfium,
+    #[cfg(feature = "hayro")]
+    Hayro,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "pdfium")]
+            Self::Pdfium => "pdfium",
+            #[cfg(feature = "hayro")]
+            Self::Hayro => "hayro",
+        }
+    }
+
+    /// How many threads can use this backend at once. libpdfium is not
+    /// thread safe, so pdfium-render only lets one instance be bound at a
+    /// time and blocks any other thread that tries.
+    pub fn max_workers(self) -> usize {
+        match self {
+            #[cfg(feature = "pdfium")]
+            Self::Pdfium => 1,
+            #[cfg(feature = "hayro")]
+            Self::Hayro => usize::MAX,
+        }
+    }
+
+    /// Create a new rasterizer. Rasterizers are not necessarily [`Send`],
+    /// so this should be called on the thread that will use it.
+    pub fn load(self) -> Result<Box<dyn Rasterizer>, RasterizeError> {
+        match self {
+            #[cfg(feature = "pdfium")]
+            Self::Pdfium => Ok(Box::new(PdfiumRasterizer::new()?)),
+            #[cfg(feature = "hayro")]
+            Self::Hayro => Ok(Box::new(HayroRasterizer)),
+        }
+    }
+
+    /// Pick the best backend that is also usable at runtime. Pdfium is
+    /// preferred, but it is skipped if the library cannot be loaded.
+    pub fn detect() -> Option<Self> {
+        let backend: Option<Self> = None;
+
+        #[cfg(feature = "pdfium")]
+        let backend = backend.or_else(|| match PdfiumRasterizer::new() {
+            Ok(_) => Some(Self::Pdfium),
+            Err(e) => {
+                warn!("libpdfium is unavailable: {e:?}");
+                None
+            }
+        });
+
+        #[cfg(feature = "hayro")]
+        let backend = backend.or(Some(Self::Hayro));
+
+        match backend {
+            Some(b) => info!("rasterizing floorplans with {}", b.name()),
+            None => warn!("no pdf rasterizer available; floorplans will be served as pdf"),
+        }
+
+        backend
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -27,22 +125,141 @@ pub enum ToImageError {
     Http(#[from] reqwest::Error),
     #[error("image error")]
     Image(#[from] image::error::ImageError),
-    #[error("pdfium error")]
-    Pdfium(#[from] PdfiumError),
+    #[error(transparent)]
+    Rasterize(#[from] RasterizeError),
+    #[error(transparent)]
+    Pool(#[from] PoolError),
     #[error("no pages in pdf")]
     NoPages,
+    #[error("no pdf backend available")]
+    NoBackend,
+    #[error("download is larger than {0} bytes")]
+    TooLarge(usize),
+    #[error("unexpected content type {0:?}")]
+    ContentType(String),
+}
+
+/// Read the body of a floorplan response, making sure that it claims to be
+/// a PDF or an image and is not unreasonably large.
+pub(crate) async fn download(res: reqwest::Response) -> Result<Bytes, ToImageError> {
+    download_limited(res, MAX_DOWNLOAD_SIZE, |mime| {
+        mime == "application/pdf"
+            || mime == "application/octet-stream"
+            || mime.starts_with("image/")
+    })
+    .await
+}
+
+/// Read the body of a response, making sure that `accept` allows its
+/// (lowercase) MIME type and that it is at most `max_size` bytes.
+#[tracing::instrument(skip_all, fields(bytes))]
+pub(crate) async fn download_limited(
+    mut res: reqwest::Response,
+    max_size: usize,
+    accept: impl Fn(&str) -> bool,
+) -> Result<Bytes, ToImageError> {
+    let content_type = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    if !accept(&mime) {
+        return Err(ToImageError::ContentType(content_type.to_owned()));
+    }
+
+    if res
+        .content_length()
+        .is_some_and(|len| len > max_size as u64)
+    {
+        return Err(ToImageError::TooLarge(max_size));
+    }
+
+    // the content length is only a hint, so keep counting
+    let mut buf = Vec::new();
+    while let Some(chunk) = res.chunk().await? {
+        if buf.len() + chunk.len() > max_size {
+            return Err(ToImageError::TooLarge(max_size));
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    tracing::Span::current().record("bytes", buf.len());
+
+    Ok(buf.into())
+}
+
+/// A floorplan, preferably as an image.
+pub enum Floorplan {
+    Image(RgbaImage),
+    /// The original PDF, returned when there is no rasterizer to render it.
+    Pdf(Bytes),
 }
 
-/// Attempt to convert an HTTP response to an [`RgbaImage`].
-pub async fn to_image(res: reqwest::Response) -> Result<RgbaImage, ToImageError> {
-    let bytes = res.bytes().await?;
+/// Attempt to convert an HTTP response to an [`RgbaImage`], falling back to
+/// the original PDF if the pool has no rasterizer.
+pub async fn to_image(
+    res: reqwest::Response,
+    pool: &RenderPool,
+) -> Result<Floorplan, ToImageError> {
+    let bytes = download(res).await?;
 
+    pool.run(move |rasterizer| {
+        let start = Instant::now();
+        let img = match image::load_from_memory(&bytes) {
+            Ok(img) => img.to_rgba8(),
+            Err(e) => match rasterizer {
+                Some(rasterizer) => rasterizer.rasterize(&bytes)?.ok_or(ToImageError::NoPages)?,
+                None if bytes.starts_with(b"%PDF") => return Ok(Floorplan::Pdf(bytes)),
+                None => return Err(e.into()),
+            },
+        };
+        histogram!("floorplan_render_duration_seconds", "op" => "rasterize")
+            .record(start.elapsed());
+
+        Ok(Floorplan::Image(img))
+    })
+    .await?
+}
+
+/// Encode a floorplan as a PNG. This doesn't need a rasterizer, so it is
+/// done off the render pool, which may only have one worker.
+pub async fn to_png(img: RgbaImage) -> Result<Vec<u8>, ToImageError> {
     tokio::task::spawn_blocking(move || {
-        let img = image::load_from_memory(&bytes)
-            .or_else(|_| pdf_to_image(bytes.to_vec())?.ok_or(ToImageError::NoPages))?;
+        let start = Instant::now();
+        let mut out = Cursor::new(Vec::new());
+        img.write_to(&mut out, ImageFormat::Png)?;
+        histogram!("floorplan_render_duration_seconds", "op" => "encode").record(start.elapsed());
 
-        Ok(img.to_rgba8())
+        Ok(out.into_inner())
     })
     .await
     .unwrap()
 }
+
+/// Extract the rooms from the text layer of a floorplan. Raster floorplans
+/// have no text layer, so no rooms are found in them.
+pub async fn rooms(res: reqwest::Response, pool: &RenderPool) -> Result<Vec<Room>, ToImageError> {
+    let bytes = download(res).await?;
+
+    if !bytes.starts_with(b"%PDF") {
+        return Ok(Vec::new());
+    }
+
+    pool.run(move |rasterizer| {
+        let start = Instant::now();
+        let spans = rasterizer
+            .ok_or(ToImageError::NoBackend)?
+            .text(&bytes)?
+            .ok_or(ToImageError::NoPages)?;
+        histogram!("floorplan_render_duration_seconds", "op" => "text").record(start.elapsed());
+
+        Ok(find_rooms(&spans))
+    })
+    .await?
+}
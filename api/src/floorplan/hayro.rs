@@ -0,0 +1,113 @@
+use hayro::{
+    hayro_interpret::{
+        font::GlyphRun, hayro_cmap::BfString, interpret_page, util::RectExt, BlendMode, ClipPath,
+        Context, Device, DrawMode, DrawProps, Image, ImageDrawProps, InterpreterCache,
+        InterpreterSettings, SoftMask,
+    },
+    hayro_syntax::Pdf,
+    kurbo::{Affine, BezPath, Point},
+    render,
+    vello_cpu::{color::palette::css::WHITE, peniko::ImageAlphaType},
+    PixmapSettings, RenderCache, RenderSettings,
+};
+use image::{imageops, RgbaImage};
+
+use super::{RasterizeError, Rasterizer, TextSpan, TARGET_SIZE};
+
+/// Pure-Rust rasterizer that needs no native libraries. It is slower than
+/// pdfium and may not support every PDF feature.
+#[derive(Debug, Default)]
+pub struct HayroRasterizer;
+
+impl Rasterizer for HayroRasterizer {
+    fn rasterize(&self, pdf: &[u8]) -> Result<Option<RgbaImage>, RasterizeError> {
+        let pdf = Pdf::new(pdf.to_vec()).map_err(RasterizeError::Hayro)?;
+
+        let Some(page) = pdf.pages().iter().next() else {
+            return Ok(None);
+        };
+
+        let (width, height) = page.render_dimensions();
+        let scale = f32::from(TARGET_SIZE) / width.max(height);
+        let pixmap = render(
+            page,
+            &RenderCache::new(),
+            &InterpreterSettings::default(),
+            &RenderSettings::default(),
+            &PixmapSettings {
+                x_scale: scale,
+                y_scale: scale,
+                bg_color: WHITE,
+            },
+        );
+
+        let (width, height) = (pixmap.width().into(), pixmap.height().into());
+        let img = RgbaImage::from_raw(width, height, pixmap.take_rgba8(ImageAlphaType::Alpha))
+            .expect("pixmap has the wrong size");
+
+        // match pdfium, which rotates landscape pages to portrait
+        if width > height {
+            Ok(Some(imageops::rotate90(&img)))
+        } else {
+            Ok(Some(img))
+        }
+    }
+
+    fn text(&self, pdf: &[u8]) -> Result<Option<Vec<TextSpan>>, RasterizeError> {
+        let pdf = Pdf::new(pdf.to_vec()).map_err(RasterizeError::Hayro)?;
+
+        let Some(page) = pdf.pages().iter().next() else {
+            return Ok(None);
+        };
+
+        let cache = InterpreterCache::new();
+        let mut context = Context::new(
+            Affine::IDENTITY,
+            page.intersected_crop_box().to_kurbo(),
+            &cache,
+            page.xref(),
+            InterpreterSettings::default(),
+        );
+        let mut device = TextDevice::default();
+        interpret_page(page, &mut context, &mut device);
+
+        Ok(Some(device.spans))
+    }
+}
+
+/// A [`Device`] that ignores everything but text.
+#[derive(Default)]
+struct TextDevice {
+    spans: Vec<TextSpan>,
+}
+
+impl<'a> Device<'a> for TextDevice {
+    fn draw_glyph_run(&mut self, run: &GlyphRun<'_, 'a>, props: DrawProps<'a>, _: &DrawMode) {
+        let Some(first) = run.glyphs().first() else {
+            return;
+        };
+        let origin = props.transform * first.transform() * Point::ORIGIN;
+
+        let mut text = String::new();
+        for glyph in run.glyphs() {
+            match glyph.as_unicode() {
+                Some(BfString::Char(c)) => text.push(c),
+                Some(BfString::String(s)) => text.push_str(&s),
+                None => {}
+            }
+        }
+
+        self.spans.push(TextSpan {
+            text,
+            x: origin.x as f32,
+            y: origin.y as f32,
+        });
+    }
+
+    fn draw_path(&mut self, _: &BezPath, _: DrawProps<'a>, _: &DrawMode) {}
+    fn push_clip_path(&mut self, _: &ClipPath) {}
+    fn push_transparency_group(&mut self, _: f32, _: Option<SoftMask<'a>>, _: BlendMode) {}
+    fn draw_image(&mut self, _: Image<'a, '_>, _: ImageDrawProps<'a>) {}
+    fn pop_clip(&mut self) {}
+    fn pop_transparency_group(&mut self) {}
+}
@@ -0,0 +1,67 @@
+use image::RgbaImage;
+use pdfium_render::{
+    error::PdfiumError, page::PdfPageRenderRotation, pdfium::Pdfium, prelude::PdfRenderConfig,
+};
+
+use super::{RasterizeError, Rasterizer, TextSpan, TARGET_SIZE};
+
+/// Rasterizer backed by a dynamically loaded libpdfium.
+#[derive(Debug)]
+pub struct PdfiumRasterizer {
+    pdfium: Pdfium,
+}
+
+impl PdfiumRasterizer {
+    /// Bind to libpdfium, either in the working directory or on the
+    /// system library path.
+    pub fn new() -> Result<Self, PdfiumError> {
+        let bindings = Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path("./"))
+            .or_else(|_| Pdfium::bind_to_system_library())?;
+
+        Ok(Self {
+            pdfium: Pdfium::new(bindings),
+        })
+    }
+}
+
+impl Rasterizer for PdfiumRasterizer {
+    fn rasterize(&self, pdf: &[u8]) -> Result<Option<RgbaImage>, RasterizeError> {
+        let document = self.pdfium.load_pdf_from_byte_slice(pdf, None)?;
+
+        let render_config = PdfRenderConfig::new()
+            .set_target_width(TARGET_SIZE.into())
+            .set_maximum_height(TARGET_SIZE.into())
+            .rotate_if_landscape(PdfPageRenderRotation::Degrees90, true);
+
+        let Some(page) = document.pages().iter().next() else {
+            return Ok(None);
+        };
+        let bitmap = page.render_with_config(&render_config)?;
+
+        Ok(Some(bitmap.as_image().to_rgba8()))
+    }
+
+    fn text(&self, pdf: &[u8]) -> Result<Option<Vec<TextSpan>>, RasterizeError> {
+        let document = self.pdfium.load_pdf_from_byte_slice(pdf, None)?;
+
+        let Some(page) = document.pages().iter().next() else {
+            return Ok(None);
+        };
+        let spans = page
+            .text()?
+            .segments()
+            .iter()
+            .map(|segment| {
+                let bounds = segment.bounds();
+
+                TextSpan {
+                    text: segment.text(),
+                    x: bounds.left.value,
+                    y: bounds.bottom.value,
+                }
+            })
+            .collect();
+
+        Ok(Some(spans))
+    }
+}
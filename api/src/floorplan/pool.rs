@@ -0,0 +1,204 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    oneshot,
+};
+use tracing::{error, info_span, warn};
+
+use super::{Backend, Rasterizer};
+
+type Job = Box<dyn FnOnce(Option<&dyn Rasterizer>) + Send>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PoolError {
+    #[error("too many floorplans are being rendered")]
+    Busy,
+    #[error("rendering timed out")]
+    Timeout,
+    #[error("render worker crashed")]
+    Crashed,
+}
+
+/// A fixed set of threads doing CPU-heavy floorplan work.
+///
+/// Every worker keeps its own rasterizer for as long as it lives, so that
+/// e.g. libpdfium is only bound once. There are never more workers than
+/// the backend allows ([`Backend::max_workers`]), which for pdfium is a
+/// single one. Jobs are queued in a bounded channel and rejected with
+/// [`PoolError::Busy`] once it is full.
+#[derive(Debug, Clone)]
+pub struct RenderPool {
+    tx: mpsc::Sender<Job>,
+    backend: Option<Backend>,
+    workers: usize,
+    timeout: Duration,
+}
+
+impl RenderPool {
+    pub fn new(
+        backend: Option<Backend>,
+        workers: usize,
+        queue_size: usize,
+        timeout: Duration,
+    ) -> Self {
+        let max = backend.map_or(usize::MAX, Backend::max_workers);
+        if let Some(b) = backend.filter(|_| workers > max) {
+            warn!("{} can only be used by {max} render worker(s)", b.name());
+        }
+        let workers = workers.min(max);
+
+        let (tx, rx) = mpsc::channel::<Job>(queue_size);
+        let rx = Arc::new(Mutex::new(rx));
+
+        for i in 0..workers {
+            let rx = rx.clone();
+
+            thread::Builder::new()
+                .name(format!("render-{i}"))
+                .spawn(move || {
+                    let rasterizer = backend.and_then(|b| {
+                        b.load()
+                            .inspect_err(|e| error!("failed to load {}: {e:?}", b.name()))
+                            .ok()
+                    });
+
+                    loop {
+                        // the lock is released as soon as a job is received
+                        let Some(job) = rx.lock().unwrap().blocking_recv() else {
+                            break;
+                        };
+                        // a panicking job drops its reply channel, which
+                        // the caller sees as `PoolError::Crashed`
+                        let _ =
+                            panic::catch_unwind(AssertUnwindSafe(|| job(rasterizer.as_deref())));
+                    }
+                })
+                .expect("failed to spawn render worker");
+        }
+
+        Self {
+            tx,
+            backend,
+            workers,
+            timeout,
+        }
+    }
+
+    /// The number of worker threads.
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    /// The backend that the workers were asked to load.
+    pub fn backend(&self) -> Option<Backend> {
+        self.backend
+    }
+
+    /// Run `f` on a worker thread. `f` receives the worker's rasterizer,
+    /// if there is one.
+    ///
+    /// Jobs whose caller has given up are skipped, but neither pdfium nor
+    /// hayro can be interrupted, so a job that has started keeps its worker
+    /// busy until it finishes, even after [`PoolError::Timeout`].
+    pub async fn run<T, F>(&self, f: F) -> Result<T, PoolError>
+    where
+        T: Send + 'static,
+        F: FnOnce(Option<&dyn Rasterizer>) -> T + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        // created here to be a child of the caller's span, and entered on
+        // the worker, so that time spent queueing shows as a gap before it
+        let span = info_span!("render");
+
+        let job: Job = Box::new(move |rasterizer| {
+            let _span = span.enter();
+
+            // skip the work if the caller has given up already
+            if !tx.is_closed() {
+                let _ = tx.send(f(rasterizer));
+            }
+        });
+        self.tx.try_send(job).map_err(|e| match e {
+            TrySendError::Full(_) => PoolError::Busy,
+            TrySendError::Closed(_) => PoolError::Crashed,
+        })?;
+
+        match tokio::time::timeout(self.timeout, rx).await {
+            Ok(Ok(v)) => Ok(v),
+            Ok(Err(_)) => Err(PoolError::Crashed),
+            Err(_) => Err(PoolError::Timeout),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Barrier},
+        time::Duration,
+    };
+
+    #[cfg(feature = "pdfium")]
+    use super::Backend;
+    use super::{PoolError, RenderPool};
+
+    #[tokio::test]
+    async fn rejects_when_full() {
+        let pool = RenderPool::new(None, 1, 1, Duration::from_millis(200));
+        let slow = || pool.run(|_| std::thread::sleep(Duration::from_millis(100)));
+
+        let (a, b, c) = tokio::join!(slow(), slow(), slow());
+        let busy = [a, b, c]
+            .into_iter()
+            .filter(|r| matches!(r, Err(PoolError::Busy)))
+            .count();
+        assert!(busy >= 1);
+    }
+
+    #[tokio::test]
+    async fn runs_in_parallel() {
+        let pool = RenderPool::new(None, 2, 2, Duration::from_secs(1));
+        let wait = Arc::new(Barrier::new(2));
+        let job = || {
+            let wait = wait.clone();
+            pool.run(move |_| wait.wait())
+        };
+
+        // deadlocks unless both jobs run at once
+        let (a, b) = tokio::join!(job(), job());
+        assert!(a.is_ok() && b.is_ok());
+    }
+
+    #[cfg(feature = "pdfium")]
+    #[tokio::test]
+    async fn one_pdfium_worker() {
+        // loading libpdfium may fail here, which doesn't matter
+        let pool = RenderPool::new(Some(Backend::Pdfium), 4, 4, Duration::from_secs(1));
+        assert_eq!(pool.workers(), 1);
+        assert_eq!(pool.run(|_| 1).await.unwrap(), 1);
+        assert_eq!(pool.run(|_| 2).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn times_out() {
+        let pool = RenderPool::new(None, 1, 1, Duration::from_millis(10));
+        let res = pool
+            .run(|_| std::thread::sleep(Duration::from_millis(100)))
+            .await;
+        assert!(matches!(res, Err(PoolError::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn survives_panics() {
+        let pool = RenderPool::new(None, 1, 1, Duration::from_secs(1));
+        let res = pool.run(|_| panic!("oh no")).await;
+        assert!(matches!(res, Err(PoolError::Crashed)));
+        assert_eq!(pool.run(|_| 1).await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,151 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Labels further than this (in points) from an area are not considered to
+/// belong to it.
+const MAX_LABEL_DISTANCE: f32 = 50.0;
+
+/// A run of text on a PDF page. The position is that of the first glyph,
+/// in PDF user space (points, with the origin in the lower left corner).
+#[derive(Debug, Clone)]
+pub struct TextSpan {
+    pub text: String,
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Room {
+    pub name: Option<String>,
+    pub area_sqm: f32,
+}
+
+/// Parse text like `"Sovrum 11,2 m²"` into the area and whatever precedes it.
+fn parse_area(s: &str) -> Option<(f32, &str)> {
+    let s = s.trim();
+    let s = ["m²", "m2", "M²", "M2", "kvm"]
+        .iter()
+        .find_map(|unit| s.strip_suffix(unit))?
+        .trim_end();
+
+    let number_start = s
+        .rfind(|c: char| !(c.is_ascii_digit() || c == ',' || c == '.'))
+        .map_or(0, |i| i + s[i..].chars().next().unwrap().len_utf8());
+    let area = s[number_start..].replace(',', ".").parse().ok()?;
+
+    Some((area, s[..number_start].trim()))
+}
+
+fn is_label(s: &str) -> bool {
+    let s = s.trim();
+    s.len() <= 30 && s.chars().any(char::is_alphabetic)
+}
+
+/// "SOVRUM" -> "Sovrum"
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    let first = chars.next().into_iter().flat_map(char::to_uppercase);
+    let rest = chars.flat_map(char::to_lowercase);
+
+    first.chain(rest).collect()
+}
+
+/// Find room sizes in the text of a floorplan. Areas without a label on the
+/// same line are matched with the closest unused label nearby, preferring
+/// labels above the area, as that is how most blueprints are drawn.
+pub fn find_rooms(spans: &[TextSpan]) -> Vec<Room> {
+    let mut areas = Vec::new();
+    let mut labels = Vec::new();
+
+    for span in spans {
+        match parse_area(&span.text) {
+            Some((area, label)) => areas.push((span, area, label)),
+            None if is_label(&span.text) => labels.push(Some(span)),
+            None => {}
+        }
+    }
+
+    areas
+        .into_iter()
+        .map(|(span, area_sqm, label)| {
+            let name = if is_label(label) {
+                Some(label)
+            } else {
+                labels
+                    .iter_mut()
+                    .filter_map(|l| {
+                        let l_span = (*l)?;
+                        let dx = l_span.x - span.x;
+                        let dy = l_span.y - span.y;
+                        // labels below the area are penalized
+                        let dist = dx.hypot(if dy < 0.0 { dy * 2.0 } else { dy });
+                        (dist <= MAX_LABEL_DISTANCE).then_some((l, dist))
+                    })
+                    .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                    .and_then(|(l, _)| l.take())
+                    .map(|l| l.text.trim())
+            };
+
+            Room {
+                name: name.map(capitalize),
+                area_sqm,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_rooms, parse_area, Room, TextSpan};
+
+    fn span(text: &str, x: f32, y: f32) -> TextSpan {
+        TextSpan {
+            text: text.to_owned(),
+            x,
+            y,
+        }
+    }
+
+    #[test]
+    fn areas() {
+        assert_eq!(parse_area("19,8 m²"), Some((19.8, "")));
+        assert_eq!(parse_area("RUM 19.8m2"), Some((19.8, "RUM")));
+        assert_eq!(parse_area("Kök 7 kvm"), Some((7.0, "Kök")));
+        assert_eq!(parse_area("m²"), None);
+        assert_eq!(parse_area("Balkong"), None);
+    }
+
+    #[test]
+    fn rooms() {
+        let spans = [
+            span("SOVRUM", 100.0, 320.0),
+            span("KÖK", 300.0, 320.0),
+            span("11,2 m²", 100.0, 305.0),
+            span("7,5 m²", 300.0, 305.0),
+            span("Balkong 4 m²", 500.0, 100.0),
+            span("2,4 m²", 800.0, 800.0),
+        ];
+
+        assert_eq!(
+            find_rooms(&spans),
+            [
+                Room {
+                    name: Some("Sovrum".to_owned()),
+                    area_sqm: 11.2
+                },
+                Room {
+                    name: Some("Kök".to_owned()),
+                    area_sqm: 7.5
+                },
+                Room {
+                    name: Some("Balkong".to_owned()),
+                    area_sqm: 4.0
+                },
+                Room {
+                    name: None,
+                    area_sqm: 2.4
+                },
+            ]
+        );
+    }
+}
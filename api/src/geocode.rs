@@ -0,0 +1,514 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use afbostader::{Address, Property};
+use axum::{
+    async_trait,
+    response::{IntoResponse, Response},
+};
+use metrics::counter;
+use reqwest::{header, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::{
+    sync::{Mutex, Semaphore},
+    time::{sleep_until, Instant},
+};
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::db::{Db, DbError, GEOCODE};
+
+/// How long to remember that an address wasn't found, in seconds, in case
+/// it gets added to the map.
+const NOT_FOUND_TTL: i64 = 7 * 24 * 60 * 60;
+
+/// Addresses that requests can wait to have geocoded at once. Geocoding
+/// is slow, so there is no point in queueing up more.
+const MAX_QUEUED: usize = 10;
+
+mod areas;
+mod gazetteer;
+mod nominatim;
+mod photon;
+
+pub use gazetteer::{Gazetteer, GazetteerError};
+pub use nominatim::Nominatim;
+pub use photon::Photon;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GeocodeQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub street: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub postalcode: Option<String>,
+}
+
+impl GeocodeQuery {
+    /// Normalize the address so that trivially different spellings of the
+    /// same address share a cache entry.
+    fn cache_key(&self) -> String {
+        fn words(s: &Option<String>) -> String {
+            s.as_deref()
+                .unwrap_or_default()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .to_lowercase()
+        }
+
+        let postal_code: String = self
+            .postalcode
+            .as_deref()
+            .unwrap_or_default()
+            .chars()
+            .filter(char::is_ascii_digit)
+            .collect();
+
+        format!(
+            "{}|{}|{}",
+            words(&self.street),
+            postal_code,
+            words(&self.city)
+        )
+    }
+}
+
+/// Spaces out requests to a geocoding service.
+pub(crate) struct Throttle {
+    interval: Duration,
+    next_request: Mutex<Instant>,
+}
+
+impl Throttle {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next_request: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until it is time for the next request.
+    pub(crate) async fn wait(&self) {
+        // requests queue up here, in order
+        let mut next_request = self.next_request.lock().await;
+        sleep_until(*next_request).await;
+        *next_request = Instant::now() + self.interval;
+    }
+}
+
+/// Make sure that relative paths can be joined onto `url`.
+pub(crate) fn base_url(mut url: Url) -> Url {
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+
+    url
+}
+
+impl From<&Address> for GeocodeQuery {
+    fn from(address: &Address) -> Self {
+        Self {
+            street: Some(address.street.clone()),
+            city: Some(address.city.clone()),
+            postalcode: Some(address.postal_code.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Place {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// A place as Nominatim gives it, with the coordinates as strings, which
+/// the unversioned `/geocode` route used to pass on.
+#[derive(Debug, Serialize)]
+pub struct NominatimPlace {
+    pub lat: String,
+    pub lon: String,
+}
+
+impl From<Place> for NominatimPlace {
+    fn from(place: Place) -> Self {
+        Self {
+            lat: place.lat.to_string(),
+            lon: place.lon.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GeocodeError {
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error("invalid coordinates from geocoder")]
+    InvalidCoordinates,
+    #[error(transparent)]
+    Db(#[from] DbError),
+    #[error("too many addresses are being geocoded")]
+    Busy,
+    #[error("too many addresses geocoded, try again in {} seconds", .0.as_secs() + 1)]
+    RateLimited(Duration),
+}
+
+impl IntoResponse for GeocodeError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Busy => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(header::RETRY_AFTER, "1")],
+                self.to_string(),
+            )
+                .into_response(),
+            Self::RateLimited(retry_after) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, (retry_after.as_secs() + 1).to_string())],
+                self.to_string(),
+            )
+                .into_response(),
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response(),
+        }
+    }
+}
+
+/// Something that turns addresses into coordinates.
+#[async_trait]
+pub trait Geocoder: Send + Sync {
+    async fn geocode(&self, query: &GeocodeQuery) -> Result<Option<Place>, GeocodeError>;
+
+    /// Identifies the provider in the cache, so that switching to another
+    /// doesn't serve the old one's answers.
+    fn name(&self) -> &'static str;
+}
+
+/// An answer from a [`Geocoder`], as it is stored.
+#[derive(Debug, Serialize, Deserialize)]
+struct Cached {
+    place: Option<Place>,
+    /// When the address was geocoded, as a Unix timestamp.
+    at: i64,
+}
+
+impl Cached {
+    fn is_fresh(&self, now: OffsetDateTime) -> bool {
+        self.place.is_some() || now.unix_timestamp() - self.at < NOT_FOUND_TTL
+    }
+}
+
+/// Remembers every answer from a [`Geocoder`] for the addresses that AF
+/// lists in the database. That an address wasn't found is only remembered
+/// for a week.
+#[derive(Clone)]
+pub struct GeocodeCache {
+    provider: Arc<dyn Geocoder>,
+    db: Db,
+    /// Lets requests wait for at most [`MAX_QUEUED`] addresses at once.
+    queue: Arc<Semaphore>,
+    /// The cache keys of the addresses of current vacancies.
+    listed: Arc<RwLock<HashSet<String>>>,
+}
+
+impl GeocodeCache {
+    pub fn new(provider: Arc<dyn Geocoder>, db: Db) -> Self {
+        Self {
+            provider,
+            db,
+            queue: Arc::new(Semaphore::new(MAX_QUEUED)),
+            listed: Default::default(),
+        }
+    }
+
+    /// Remember which addresses AF lists, which are the only ones that
+    /// are stored.
+    pub fn set_listed(&self, vacancies: &[Property]) {
+        *self.listed.write().unwrap() = vacancies
+            .iter()
+            .map(|p| GeocodeQuery::from(&p.address).cache_key())
+            .collect();
+    }
+
+    fn is_listed(&self, query: &GeocodeQuery) -> bool {
+        self.listed.read().unwrap().contains(&query.cache_key())
+    }
+
+    /// Whether geocoding `query` means asking the provider about an
+    /// address that AF doesn't list.
+    pub async fn is_ad_hoc(&self, query: &GeocodeQuery) -> Result<bool, DbError> {
+        Ok(!self.is_listed(query) && self.stored(query).await?.is_none())
+    }
+
+    /// Geocode `query` for a request. Addresses that AF lists are stored,
+    /// while others are only looked up if `ad_hoc` is set. Fails with
+    /// [`GeocodeError::Busy`] rather than queueing up behind too many
+    /// other addresses.
+    pub async fn lookup(
+        &self,
+        query: &GeocodeQuery,
+        ad_hoc: bool,
+    ) -> Result<Option<Place>, GeocodeError> {
+        if let Some(place) = self.cached(query).await? {
+            return Ok(place);
+        }
+
+        let listed = self.is_listed(query);
+        if !listed && !ad_hoc {
+            return Ok(None);
+        }
+
+        let _permit = self.queue.try_acquire().map_err(|_| GeocodeError::Busy)?;
+
+        if listed {
+            self.fetch(query).await
+        } else {
+            self.provider.geocode(query).await
+        }
+    }
+
+    fn key(&self, query: &GeocodeQuery) -> String {
+        format!("{}|{}", self.provider.name(), query.cache_key())
+    }
+
+    async fn stored(&self, query: &GeocodeQuery) -> Result<Option<Option<Place>>, DbError> {
+        let now = OffsetDateTime::now_utc();
+        let cached = self
+            .db
+            .get::<Cached>(GEOCODE, &self.key(query))
+            .await?
+            .filter(|cached| cached.is_fresh(now))
+            .map(|cached| cached.place);
+
+        Ok(cached)
+    }
+
+    /// Look up `query` without asking the provider, counting it as a cache
+    /// hit or miss. The outer `Option` is `None` if the address has never
+    /// been geocoded, or wasn't found a while ago.
+    pub async fn cached(&self, query: &GeocodeQuery) -> Result<Option<Option<Place>>, DbError> {
+        let cached = self.stored(query).await?;
+        counter!(
+            "geocode_cache_lookups_total",
+            "result" => if cached.is_some() { "hit" } else { "miss" },
+        )
+        .increment(1);
+
+        Ok(cached)
+    }
+
+    /// Ask the provider about `query`, and store the answer.
+    async fn fetch(&self, query: &GeocodeQuery) -> Result<Option<Place>, GeocodeError> {
+        let place = self.provider.geocode(query).await?;
+        let cached = Cached {
+            place,
+            at: OffsetDateTime::now_utc().unix_timestamp(),
+        };
+        self.db.insert(GEOCODE, &self.key(query), &cached).await?;
+
+        Ok(place)
+    }
+
+    /// Find the coordinates of a property without waiting for the provider,
+    /// falling back to the center of its area if the address is not cached.
+    pub async fn locate(&self, property: &Property) -> Option<Place> {
+        let cached = self
+            .cached(&(&property.address).into())
+            .await
+            .inspect_err(|e| warn!("failed to read geocode cache: {e}"))
+            .ok()
+            .flatten()
+            .flatten();
+
+        cached.or_else(|| areas::center(&property.area))
+    }
+}
+
+#[async_trait]
+impl Geocoder for GeocodeCache {
+    async fn geocode(&self, query: &GeocodeQuery) -> Result<Option<Place>, GeocodeError> {
+        if let Some(place) = self.cached(query).await? {
+            return Ok(place);
+        }
+
+        self.fetch(query).await
+    }
+
+    fn name(&self) -> &'static str {
+        self.provider.name()
+    }
+}
+
+/// Geocode the addresses of all current vacancies, so that the map loads
+/// from the cache.
+pub async fn prewarm(geocoder: &GeocodeCache, vacancies: &[afbostader::Property]) {
+    geocoder.set_listed(vacancies);
+
+    for property in vacancies {
+        if let Err(e) = geocoder.geocode(&(&property.address).into()).await {
+            warn!("failed to geocode {:?}: {e}", property.address);
+        }
+    }
+
+    info!("geocoded {} vacancies", vacancies.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use axum::async_trait;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use time::{Duration, OffsetDateTime};
+
+    use super::{
+        Cached, GeocodeCache, GeocodeError, GeocodeQuery, Geocoder, NominatimPlace, Place,
+        NOT_FOUND_TTL,
+    };
+    use crate::{db::Db, testing};
+
+    #[derive(Default)]
+    struct Stub(AtomicUsize);
+
+    #[async_trait]
+    impl Geocoder for Stub {
+        async fn geocode(&self, _: &GeocodeQuery) -> Result<Option<Place>, GeocodeError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+
+            Ok(Some(Place {
+                lat: 55.7,
+                lon: 13.2,
+            }))
+        }
+
+        fn name(&self) -> &'static str {
+            "stub"
+        }
+    }
+
+    #[tokio::test]
+    async fn only_stores_listed_addresses() {
+        let stub = Arc::new(Stub::default());
+        let cache = GeocodeCache::new(stub.clone(), Db::in_memory().unwrap());
+        let property = testing::property();
+        cache.set_listed(std::slice::from_ref(&property));
+
+        let listed = GeocodeQuery::from(&property.address);
+        let other = GeocodeQuery {
+            street: Some("Stortorget 1".to_owned()),
+            ..Default::default()
+        };
+
+        assert!(!cache.is_ad_hoc(&listed).await.unwrap());
+        assert!(cache.is_ad_hoc(&other).await.unwrap());
+
+        // not looked up unless asked to
+        assert!(cache.lookup(&other, false).await.unwrap().is_none());
+        assert_eq!(stub.0.load(Ordering::SeqCst), 0);
+
+        for _ in 0..2 {
+            assert!(cache.lookup(&listed, false).await.unwrap().is_some());
+            assert!(cache.lookup(&other, true).await.unwrap().is_some());
+        }
+        // the listed address was cached, the other one wasn't
+        assert_eq!(stub.0.load(Ordering::SeqCst), 3);
+        assert!(cache.is_ad_hoc(&other).await.unwrap());
+    }
+
+    #[test]
+    fn counts_lookups() {
+        let cache = GeocodeCache::new(Arc::new(Stub::default()), Db::in_memory().unwrap());
+        let property = testing::property();
+        let mut elsewhere = testing::property();
+        elsewhere.address.street = "Stortorget 1".to_owned();
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        metrics::with_local_recorder(&recorder, || {
+            rt.block_on(async {
+                cache.geocode(&(&property.address).into()).await.unwrap();
+                cache.locate(&property).await;
+                cache.locate(&elsewhere).await;
+            })
+        });
+
+        let counts: Vec<_> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .filter(|(key, ..)| key.key().name() == "geocode_cache_lookups_total")
+            .map(|(key, _, _, value)| {
+                let result = key.key().labels().next().unwrap().value().to_owned();
+                (result, value)
+            })
+            .collect();
+        // the first lookup misses and stores the address, which `locate`
+        // then finds
+        assert_eq!(counts.len(), 2);
+        for (result, value) in counts {
+            assert_eq!(
+                value,
+                DebugValue::Counter(if result == "hit" { 1 } else { 2 })
+            );
+        }
+    }
+
+    #[test]
+    fn nominatim_places() {
+        let place = Place {
+            lat: 55.7117,
+            lon: 13.2098,
+        };
+
+        assert_eq!(
+            serde_json::to_value([NominatimPlace::from(place)]).unwrap(),
+            serde_json::json!([{ "lat": "55.7117", "lon": "13.2098" }]),
+        );
+    }
+
+    #[test]
+    fn forgets_not_found() {
+        let now = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let not_found = Cached {
+            place: None,
+            at: now.unix_timestamp(),
+        };
+        let found = Cached {
+            place: Some(Place { lat: 0.0, lon: 0.0 }),
+            ..not_found
+        };
+        let later = now + Duration::seconds(NOT_FOUND_TTL);
+
+        assert!(not_found.is_fresh(now));
+        assert!(!not_found.is_fresh(later));
+        assert!(found.is_fresh(later));
+    }
+
+    #[test]
+    fn cache_key() {
+        let a = GeocodeQuery {
+            street: Some("Tornavägen  9".to_owned()),
+            city: Some("LUND".to_owned()),
+            postalcode: Some("223 63".to_owned()),
+        };
+        let b = GeocodeQuery {
+            street: Some("tornavägen 9 ".to_owned()),
+            city: Some("Lund".to_owned()),
+            postalcode: Some("22363".to_owned()),
+        };
+
+        assert_eq!(a.cache_key(), b.cache_key());
+    }
+}
@@ -0,0 +1,44 @@
+use afbostader::Area;
+
+use super::Place;
+
+/// Approximate centers of the AF Bostäder areas, used when an address has
+/// not been (or cannot be) geocoded. Good enough to put a pin on the map.
+///
+/// Areas are keyed by the slug of their page, so that names that AF gives
+/// the page of another area (e.g. Parentesen, which is part of Sparta) get
+/// the center of that area.
+const AREAS: &[(&str, f64, f64)] = &[
+    ("arkivet", 55.7132, 13.2020),
+    ("delphi", 55.7205, 13.2095),
+    ("kamnarsvagen", 55.7185, 13.2150),
+    ("klostergarden", 55.6925, 13.1830),
+    ("michael-hansen", 55.6990, 13.1920),
+    ("rhodos", 55.7190, 13.2155),
+    ("sparta", 55.7150, 13.2065),
+    ("ulrikedal", 55.7165, 13.1950),
+    ("vildanden", 55.7195, 13.2190),
+];
+
+/// The center of the named area.
+pub fn center(area: &str) -> Option<Place> {
+    let slug = Area::from_name(area).slug;
+
+    AREAS
+        .iter()
+        .find(|(s, ..)| *s == slug)
+        .map(|&(_, lat, lon)| Place { lat, lon })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::center;
+
+    #[test]
+    fn aliases() {
+        assert!(center(" Klostergården ").is_some());
+        assert_eq!(center("Parentesen"), center("Sparta"));
+        assert!(center("Michael Hansens kollegium").is_some());
+        assert!(center("Nowhere").is_none());
+    }
+}
@@ -0,0 +1,104 @@
+use std::{collections::HashMap, io, path::Path};
+
+use axum::async_trait;
+use serde::Deserialize;
+
+use super::{GeocodeError, GeocodeQuery, Geocoder, Place};
+
+#[derive(Debug, thiserror::Error)]
+pub enum GazetteerError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+}
+
+/// An offline list of addresses, read from a CSV file with the columns
+/// `street,postal_code,city,lat,lon`.
+pub struct Gazetteer {
+    places: HashMap<String, Place>,
+}
+
+impl Gazetteer {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, GazetteerError> {
+        Self::from_reader(std::fs::File::open(path)?)
+    }
+
+    pub fn from_reader(reader: impl io::Read) -> Result<Self, GazetteerError> {
+        #[derive(Deserialize)]
+        struct Row {
+            street: String,
+            postal_code: String,
+            city: String,
+            lat: f64,
+            lon: f64,
+        }
+
+        let mut places = HashMap::new();
+
+        for row in csv::Reader::from_reader(reader).deserialize() {
+            let row: Row = row?;
+            let query = GeocodeQuery {
+                street: Some(row.street),
+                city: Some(row.city),
+                postalcode: Some(row.postal_code),
+            };
+
+            places.insert(
+                query.cache_key(),
+                Place {
+                    lat: row.lat,
+                    lon: row.lon,
+                },
+            );
+        }
+
+        Ok(Self { places })
+    }
+
+    pub fn len(&self) -> usize {
+        self.places.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.places.is_empty()
+    }
+}
+
+#[async_trait]
+impl Geocoder for Gazetteer {
+    async fn geocode(&self, query: &GeocodeQuery) -> Result<Option<Place>, GeocodeError> {
+        Ok(self.places.get(&query.cache_key()).copied())
+    }
+
+    fn name(&self) -> &'static str {
+        "gazetteer"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Gazetteer;
+    use crate::geocode::{GeocodeQuery, Geocoder};
+
+    #[tokio::test]
+    async fn lookup() {
+        let csv = "street,postal_code,city,lat,lon\n\
+                   Kämnärsvägen 24,226 45,Lund,55.7188,13.2151\n";
+        let gazetteer = Gazetteer::from_reader(csv.as_bytes()).unwrap();
+
+        let query = GeocodeQuery {
+            street: Some("KÄMNÄRSVÄGEN 24".to_owned()),
+            city: Some("LUND".to_owned()),
+            postalcode: Some("22645".to_owned()),
+        };
+        let place = gazetteer.geocode(&query).await.unwrap().unwrap();
+        assert_eq!((place.lat, place.lon), (55.7188, 13.2151));
+
+        let query = GeocodeQuery {
+            street: Some("Kämnärsvägen 26".to_owned()),
+            ..query
+        };
+        assert!(gazetteer.geocode(&query).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use axum::async_trait;
+use reqwest::Url;
+use serde::Deserialize;
+use tracing::debug;
+
+use super::{base_url, GeocodeError, GeocodeQuery, Geocoder, Place, Throttle};
+
+/// The "absolute maximum" according to the Nominatim Usage Policy is 1
+/// request per second.
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Geocodes addresses with [Nominatim](https://nominatim.org/).
+pub struct Nominatim {
+    client: reqwest::Client,
+    url: Url,
+    throttle: Throttle,
+}
+
+impl Nominatim {
+    /// `url` is the base of the API, e.g.
+    /// `https://nominatim.openstreetmap.org/`.
+    pub fn new(client: reqwest::Client, url: Url) -> Self {
+        Self {
+            client,
+            url: base_url(url),
+            throttle: Throttle::new(INTERVAL),
+        }
+    }
+
+    /// Wait at least `interval` between requests, rather than a second.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.throttle = Throttle::new(interval);
+        self
+    }
+}
+
+#[async_trait]
+impl Geocoder for Nominatim {
+    async fn geocode(&self, query: &GeocodeQuery) -> Result<Option<Place>, GeocodeError> {
+        #[derive(Deserialize)]
+        struct Response {
+            lat: String,
+            lon: String,
+        }
+
+        self.throttle.wait().await;
+
+        debug!("geocoding {query:?}");
+
+        let res = self
+            .client
+            .get(self.url.join("search").unwrap())
+            .query(&[("format", "jsonv2")])
+            .query(query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Vec<Response>>()
+            .await?;
+
+        let Some(first) = res.into_iter().next() else {
+            return Ok(None);
+        };
+
+        match (first.lat.parse(), first.lon.parse()) {
+            (Ok(lat), Ok(lon)) => Ok(Some(Place { lat, lon })),
+            _ => Err(GeocodeError::InvalidCoordinates),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "nominatim"
+    }
+}
@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use axum::async_trait;
+use reqwest::Url;
+use serde::Deserialize;
+use tracing::debug;
+
+use super::{base_url, GeocodeError, GeocodeQuery, Geocoder, Place, Throttle};
+
+/// The public instance at photon.komoot.io asks for fair use, which we take
+/// to mean the same as Nominatim's limit.
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Geocodes addresses with a [Photon](https://github.com/komoot/photon)
+/// compatible API.
+pub struct Photon {
+    client: reqwest::Client,
+    url: Url,
+    throttle: Throttle,
+}
+
+impl Photon {
+    /// `url` is the base of the API, e.g. `https://photon.komoot.io/`.
+    pub fn new(client: reqwest::Client, url: Url) -> Self {
+        Self {
+            client,
+            url: base_url(url),
+            throttle: Throttle::new(INTERVAL),
+        }
+    }
+
+    /// Wait at least `interval` between requests, rather than a second.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.throttle = Throttle::new(interval);
+        self
+    }
+}
+
+#[async_trait]
+impl Geocoder for Photon {
+    async fn geocode(&self, query: &GeocodeQuery) -> Result<Option<Place>, GeocodeError> {
+        #[derive(Deserialize)]
+        struct Geometry {
+            /// `[lon, lat]`
+            coordinates: (f64, f64),
+        }
+
+        #[derive(Deserialize)]
+        struct Feature {
+            geometry: Geometry,
+        }
+
+        #[derive(Deserialize)]
+        struct Response {
+            features: Vec<Feature>,
+        }
+
+        let q = [&query.street, &query.postalcode, &query.city]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        self.throttle.wait().await;
+        debug!("geocoding {q:?}");
+
+        let res = self
+            .client
+            .get(self.url.join("api").unwrap())
+            .query(&[("q", q.as_str()), ("limit", "1")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Response>()
+            .await?;
+
+        Ok(res.features.into_iter().next().map(|f| {
+            let (lon, lat) = f.geometry.coordinates;
+            Place { lat, lon }
+        }))
+    }
+
+    fn name(&self) -> &'static str {
+        "photon"
+    }
+}
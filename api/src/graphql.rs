@@ -0,0 +1,562 @@
+//! A GraphQL endpoint, for pages that want a vacancy together with its
+//! details, area and floorplan in a single request.
+//!
+//! The types are the ones of [`v1`], and AF is called through per-request
+//! [`DataLoader`]s so that a vacancy or area asked for in several places is
+//! only fetched once.
+
+use std::{collections::HashMap, sync::Arc};
+
+use afbostader::PropertyDetail;
+
+use async_graphql::{
+    dataloader::{DataLoader, HashMapCache, Loader},
+    http::GraphiQLSource,
+    ComplexObject, Context, EmptyMutation, EmptySubscription, Object, Schema, SimpleObject,
+};
+use axum::{
+    async_trait,
+    extract::State,
+    response::{Html, IntoResponse},
+    Json,
+};
+use axum_extra::TypedHeader;
+use futures::{stream, StreamExt, TryStreamExt};
+use headers::CacheControl;
+
+use crate::{
+    commute::Commute,
+    filter::{PropertyKind, VacancyFilter},
+    v1, vacancy, AppState, PersonalAf,
+};
+
+pub type ApiSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+pub fn schema() -> ApiSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .limit_depth(10)
+        .limit_complexity(1000)
+        .finish()
+}
+
+/// The loaders only live as long as the request, so they can cache
+/// everything.
+type Cached<L> = DataLoader<L, HashMapCache>;
+
+/// Where [`VacancyLoader`] gets vacancy details from, which is AF outside
+/// of tests.
+#[async_trait]
+pub(crate) trait DetailSource: Send + Sync {
+    async fn vacancy_detail(&self, id: u32) -> Result<PropertyDetail, afbostader::Error>;
+}
+
+#[async_trait]
+impl DetailSource for afbostader::Client {
+    async fn vacancy_detail(&self, id: u32) -> Result<PropertyDetail, afbostader::Error> {
+        afbostader::Client::vacancy_detail(self, id).await
+    }
+}
+
+/// Vacancy details, by property ID. Vacancies that AF doesn't know are
+/// left out. Any other error fails the whole batch, as it is seldom about
+/// a single vacancy.
+struct VacancyLoader {
+    source: Arc<dyn DetailSource>,
+    state: AppState,
+}
+
+impl Loader<u32> for VacancyLoader {
+    type Value = Arc<v1::VacancyDetail>;
+    type Error = Arc<afbostader::Error>;
+
+    async fn load(&self, ids: &[u32]) -> Result<HashMap<u32, Self::Value>, Self::Error> {
+        // AF has no batch endpoint, so ask for a few vacancies at a time
+        let details: Vec<_> = stream::iter(ids.to_vec())
+            .map(|id| async move {
+                let detail = match self.source.vacancy_detail(id).await {
+                    Ok(detail) => detail,
+                    Err(afbostader::Error::VacancyNotFound(_)) => return Ok(None),
+                    Err(e) => return Err(Arc::new(e)),
+                };
+                let detail = vacancy::Vacancy::new(detail, &self.state).await;
+
+                Ok(Some((id, Arc::new(v1::VacancyDetail::from(detail)))))
+            })
+            .buffer_unordered(vacancy::DETAIL_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        Ok(details.into_iter().flatten().collect())
+    }
+}
+
+/// Area details, by the name of the area as given in
+/// [`v1::Vacancy::area`]. Areas without a page are left out.
+struct AreaLoader {
+    af: afbostader::Client,
+}
+
+impl Loader<String> for AreaLoader {
+    type Value = Arc<v1::AreaDetail>;
+    type Error = Arc<afbostader::Error>;
+
+    async fn load(&self, names: &[String]) -> Result<HashMap<String, Self::Value>, Self::Error> {
+        let areas = futures::future::try_join_all(names.iter().map(|name| async move {
+            match self.af.area_detail(name).await {
+                Ok(detail) => Ok(Some((name.clone(), Arc::new(detail.into())))),
+                Err(afbostader::Error::AreaNotFound(_)) => Ok(None),
+                Err(e) => Err(e),
+            }
+        }))
+        .await?;
+
+        Ok(areas.into_iter().flatten().collect())
+    }
+}
+
+pub struct Vacancy(v1::Vacancy);
+
+impl Vacancy {
+    async fn detail(&self, ctx: &Context<'_>) -> async_graphql::Result<Arc<v1::VacancyDetail>> {
+        let loader = ctx.data_unchecked::<Cached<VacancyLoader>>();
+
+        Ok(loader
+            .load_one(self.0.id)
+            .await?
+            .ok_or("vacancy not found")?)
+    }
+}
+
+#[Object]
+impl Vacancy {
+    async fn id(&self) -> u32 {
+        self.0.id
+    }
+
+    async fn description(&self) -> &str {
+        &self.0.description
+    }
+
+    async fn short_description(&self) -> &str {
+        &self.0.short_description
+    }
+
+    async fn address(&self) -> &v1::Address {
+        &self.0.address
+    }
+
+    async fn property_type(&self) -> PropertyKind {
+        (&self.0.property_type).into()
+    }
+
+    /// The name of the area, as given by AF.
+    async fn area_name(&self) -> &str {
+        &self.0.area
+    }
+
+    async fn queue_position(&self) -> &v1::QueuePosition {
+        &self.0.queue_position
+    }
+
+    async fn reserved(&self) -> bool {
+        self.0.reserved
+    }
+
+    async fn floor(&self) -> i8 {
+        self.0.floor
+    }
+
+    async fn size_sqm(&self) -> f32 {
+        self.0.size_sqm
+    }
+
+    async fn reserve_from(&self) -> time::Date {
+        self.0.reserve_from
+    }
+
+    async fn reserve_until(&self) -> time::Date {
+        self.0.reserve_until
+    }
+
+    async fn move_in(&self) -> time::Date {
+        self.0.move_in
+    }
+
+    async fn priority(&self) -> Option<&str> {
+        self.0.priority.as_deref()
+    }
+
+    /// Monthly rent in SEK.
+    async fn rent(&self) -> u32 {
+        self.0.rent
+    }
+
+    async fn lat(&self) -> Option<f64> {
+        self.0.lat
+    }
+
+    async fn lon(&self) -> Option<f64> {
+        self.0.lon
+    }
+
+    async fn commutes(&self) -> &[Commute] {
+        &self.0.commutes
+    }
+
+    /// What is only known from the vacancy's own page, which takes another
+    /// request to AF.
+    async fn details(&self, ctx: &Context<'_>) -> async_graphql::Result<Details> {
+        Ok(Details(self.detail(ctx).await?))
+    }
+
+    /// The area that the vacancy is in, if it has a page on AF's website.
+    async fn area(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Area>> {
+        let loader = ctx.data_unchecked::<Cached<AreaLoader>>();
+        let detail = loader.load_one(self.0.area.clone()).await?;
+
+        Ok(detail.map(|detail| Area {
+            name: self.0.area.clone(),
+            detail,
+        }))
+    }
+
+    /// Where to get the floorplan as an image, relative to the API, if
+    /// there is one.
+    async fn floorplan_url(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<String>> {
+        let detail = self.detail(ctx).await?;
+
+        Ok(detail
+            .blueprint
+            .is_some()
+            .then(|| format!("/v1/vacancies/{}/floorplan", self.0.id)))
+    }
+}
+
+pub struct Details(Arc<v1::VacancyDetail>);
+
+#[Object]
+impl Details {
+    async fn status(&self) -> &str {
+        &self.0.status
+    }
+
+    async fn store(&self) -> &v1::Store {
+        &self.0.store
+    }
+
+    async fn caretaker(&self) -> &v1::Caretaker {
+        &self.0.caretaker
+    }
+
+    async fn shower(&self) -> &str {
+        &self.0.shower
+    }
+
+    async fn furniture(&self) -> &str {
+        &self.0.furniture
+    }
+
+    async fn balcony(&self) -> &str {
+        &self.0.balcony
+    }
+
+    async fn kitchen(&self) -> &str {
+        &self.0.kitchen
+    }
+
+    async fn elevator(&self) -> &str {
+        &self.0.elevator
+    }
+
+    async fn heating(&self) -> &str {
+        &self.0.heating
+    }
+
+    async fn electricity(&self) -> &str {
+        &self.0.electricity
+    }
+
+    async fn internet(&self) -> &str {
+        &self.0.internet
+    }
+
+    async fn facing(&self) -> &str {
+        &self.0.facing
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct Area {
+    /// The name that the area was looked up by.
+    name: String,
+    #[graphql(skip)]
+    detail: Arc<v1::AreaDetail>,
+}
+
+/// A picture of an area, resized by the API.
+#[derive(SimpleObject)]
+pub struct AreaPicture {
+    /// Relative to the API.
+    url: String,
+    alt: Option<String>,
+}
+
+#[ComplexObject]
+impl Area {
+    async fn description(&self) -> Option<&str> {
+        self.detail.description.as_deref()
+    }
+
+    async fn facilities(&self) -> &[String] {
+        &self.detail.facilities
+    }
+
+    /// Typical rents, as free-form text.
+    async fn rents(&self) -> &[String] {
+        &self.detail.rents
+    }
+
+    async fn addresses(&self) -> &[String] {
+        &self.detail.addresses
+    }
+
+    async fn contact(&self) -> Option<&v1::Contact> {
+        self.detail.contact.as_ref()
+    }
+
+    /// At most `size` pixels wide and tall.
+    async fn pictures(&self, size: Option<u32>) -> Vec<AreaPicture> {
+        let name = url::form_urlencoded::byte_serialize(self.name.as_bytes()).collect::<String>();
+        let query = size.map(|s| format!("?size={s}")).unwrap_or_default();
+
+        self.detail
+            .pictures
+            .iter()
+            .enumerate()
+            .map(|(idx, p)| AreaPicture {
+                url: format!("/v1/areas/{name}/pictures/{idx}{query}"),
+                alt: p.alt.clone(),
+            })
+            .collect()
+    }
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    async fn vacancies(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<VacancyFilter>,
+    ) -> async_graphql::Result<Vec<Vacancy>> {
+        let af = ctx.data_unchecked::<afbostader::Client>();
+        let state = ctx.data_unchecked::<AppState>();
+        let filter = filter.unwrap_or_default();
+
+        let properties = af.list_vacancies().await?;
+        let vacancies =
+            vacancy::Vacancy::many(properties.into_iter().filter(|p| filter.matches(p)), state)
+                .await;
+
+        Ok(vacancies
+            .into_iter()
+            .map(|vacancy| Vacancy(vacancy.into()))
+            .collect())
+    }
+
+    async fn vacancy(&self, ctx: &Context<'_>, id: u32) -> async_graphql::Result<Option<Vacancy>> {
+        let loader = ctx.data_unchecked::<Cached<VacancyLoader>>();
+        let detail = loader.load_one(id).await?;
+
+        Ok(detail.map(|detail| Vacancy(detail.vacancy.clone())))
+    }
+
+    /// The logged in user, if any.
+    async fn me(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<v1::User>> {
+        let af = ctx.data_unchecked::<afbostader::Client>();
+
+        if !af.has_credentials() {
+            return Ok(None);
+        }
+
+        match af.user_info().await {
+            Ok(user) => Ok(Some(user.into())),
+            Err(afbostader::Error::BadCredentials | afbostader::Error::Unauthenticated) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Give `req` what the resolvers need, fetching vacancy details from
+/// `source`.
+fn prepare(
+    req: async_graphql::Request,
+    af: afbostader::Client,
+    source: Arc<dyn DetailSource>,
+    state: &AppState,
+) -> async_graphql::Request {
+    req.data(DataLoader::with_cache(
+        VacancyLoader {
+            source,
+            state: state.clone(),
+        },
+        tokio::spawn,
+        HashMapCache::default(),
+    ))
+    .data(DataLoader::with_cache(
+        AreaLoader { af: af.clone() },
+        tokio::spawn,
+        HashMapCache::default(),
+    ))
+    .data(af)
+    .data(state.clone())
+}
+
+pub async fn handler(
+    State(state): State<AppState>,
+    af: PersonalAf,
+    Json(req): Json<async_graphql::Request>,
+) -> impl IntoResponse {
+    let PersonalAf(af) = af;
+    let req = prepare(req, af.clone(), Arc::new(af), &state);
+
+    (
+        TypedHeader(CacheControl::new().with_private().with_no_store()),
+        Json(state.graphql.execute(req).await),
+    )
+}
+
+/// An IDE for trying out queries.
+pub async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use afbostader::PropertyDetail;
+    use async_graphql::PathSegment;
+    use axum::async_trait;
+
+    use super::{prepare, DetailSource};
+    use crate::{
+        commute::{Mode, PointOfInterest, Route, RouteError, RouteProvider},
+        geocode::Place,
+        testing,
+    };
+
+    /// Knows the saved vacancy under the IDs 1 and 2, fails to load 500,
+    /// and remembers what it was asked for.
+    #[derive(Default)]
+    struct Fixtures(Mutex<Vec<u32>>);
+
+    #[async_trait]
+    impl DetailSource for Fixtures {
+        async fn vacancy_detail(&self, id: u32) -> Result<PropertyDetail, afbostader::Error> {
+            self.0.lock().unwrap().push(id);
+
+            match id {
+                1 | 2 => {
+                    let mut detail = testing::property_detail();
+                    detail.property.id = id;
+                    Ok(detail)
+                }
+                500 => Err(afbostader::Error::Unknown("internal error".to_owned())),
+                _ => Err(afbostader::Error::VacancyNotFound(id)),
+            }
+        }
+    }
+
+    struct Straight;
+
+    #[async_trait]
+    impl RouteProvider for Straight {
+        async fn route(&self, _: Place, _: Place, _: Mode) -> Result<Option<Route>, RouteError> {
+            unreachable!("routes are cached")
+        }
+
+        async fn cached(
+            &self,
+            _: Place,
+            _: Place,
+            _: Mode,
+        ) -> Result<Option<Option<Route>>, RouteError> {
+            Ok(Some(Some(Route {
+                distance_m: 1000.0,
+                duration_s: 600.0,
+            })))
+        }
+    }
+
+    #[test]
+    fn schema() {
+        let sdl = super::schema().sdl();
+
+        assert!(sdl.contains("vacancies(filter: VacancyFilter): [Vacancy!]!"));
+        assert!(sdl.contains("floorplanUrl: String"));
+        assert!(sdl.contains("me: User"));
+    }
+
+    #[tokio::test]
+    async fn vacancies_by_id() {
+        let poi = PointOfInterest {
+            name: "LTH".to_owned(),
+            place: Place {
+                lat: 55.711,
+                lon: 13.21,
+            },
+        };
+        let state = testing::state(vec![poi], Arc::new(Straight));
+        let source = Arc::new(Fixtures::default());
+        let req = prepare(
+            "{
+                a: vacancy(id: 1) { id commutes { to } details { status } }
+                b: vacancy(id: 1) { id }
+                c: vacancy(id: 2) { id }
+                d: vacancy(id: 999) { id }
+            }"
+            .into(),
+            state.af.clone(),
+            source.clone(),
+            &state,
+        );
+
+        let res = state.graphql.execute(req).await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+
+        let data = res.data.into_json().unwrap();
+        assert_eq!(data["a"]["id"], 1);
+        assert_eq!(data["a"]["commutes"][0]["to"], "LTH");
+        assert_eq!(data["b"]["id"], 1);
+        assert_eq!(data["c"]["id"], 2);
+        assert!(data["d"].is_null());
+
+        // each vacancy is only fetched once, and the one that doesn't
+        // exist doesn't fail the others
+        let mut fetched = source.0.lock().unwrap().clone();
+        fetched.sort_unstable();
+        assert_eq!(fetched, [1, 2, 999]);
+    }
+
+    #[tokio::test]
+    async fn vacancy_errors() {
+        let state = testing::state(Vec::new(), Arc::new(Straight));
+        let req = prepare(
+            "{ broken: vacancy(id: 500) { id } }".into(),
+            state.af.clone(),
+            Arc::new(Fixtures::default()),
+            &state,
+        );
+
+        let res = state.graphql.execute(req).await;
+        assert_eq!(res.errors.len(), 1);
+        assert_eq!(res.errors[0].message, "unknown api error: internal error");
+        assert_eq!(
+            res.errors[0].path,
+            [PathSegment::Field("broken".to_owned())]
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! Liveness and readiness checks.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    db::{Db, HEALTH},
+    floorplan::{PoolError, RenderPool},
+    AppState,
+};
+
+/// How long a single dependency may take to respond.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the result of pinging AF is reused, which is how often Fly
+/// checks readiness, so that checking more often doesn't spend more
+/// requests to AF.
+const PING_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Check {
+    Ok {
+        latency_ms: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        detail: Option<String>,
+    },
+    /// Working, but not as well as it should, which doesn't stop the
+    /// service from being ready.
+    Degraded {
+        latency_ms: u64,
+        detail: String,
+    },
+    Error {
+        latency_ms: u64,
+        error: String,
+    },
+}
+
+/// What a check found, short of an error.
+enum Outcome {
+    Ok(Option<String>),
+    Degraded(String),
+}
+
+impl From<Option<String>> for Outcome {
+    fn from(detail: Option<String>) -> Self {
+        Self::Ok(detail)
+    }
+}
+
+impl Check {
+    async fn run<T, E>(f: impl Future<Output = Result<T, E>>) -> Self
+    where
+        T: Into<Outcome>,
+        E: Display,
+    {
+        let start = Instant::now();
+        let res = tokio::time::timeout(CHECK_TIMEOUT, f).await;
+        let latency_ms = start.elapsed().as_millis() as u64;
+
+        match res.map(|res| res.map(Into::into)) {
+            Ok(Ok(Outcome::Ok(detail))) => Self::Ok { latency_ms, detail },
+            Ok(Ok(Outcome::Degraded(detail))) => Self::Degraded { latency_ms, detail },
+            Ok(Err(e)) => Self::Error {
+                latency_ms,
+                error: e.to_string(),
+            },
+            Err(_) => Self::Error {
+                latency_ms,
+                error: "timed out".to_owned(),
+            },
+        }
+    }
+
+    /// Whether the check allows the service to be ready.
+    pub fn is_ok(&self) -> bool {
+        !matches!(self, Self::Error { .. })
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Readiness {
+    pub ready: bool,
+    pub checks: BTreeMap<&'static str, Check>,
+}
+
+/// When AF was last pinged, and how that went.
+type Ping = (Instant, Result<(), String>);
+
+/// The last result of pinging AF, shared by readiness checks.
+#[derive(Clone, Default)]
+pub struct AfPing(Arc<tokio::sync::Mutex<Option<Ping>>>);
+
+impl AfPing {
+    async fn check(&self, af: &afbostader::Client) -> Result<Option<String>, String> {
+        // held while pinging, so that concurrent checks share one request
+        let mut last = self.0.lock().await;
+
+        if let Some((at, res)) = &*last {
+            if at.elapsed() < PING_TTL {
+                let age = format!("checked {}s ago", at.elapsed().as_secs());
+                return res.clone().map(|()| Some(age));
+            }
+        }
+
+        let res = af.ping().await.map_err(|e| e.to_string());
+        *last = Some((Instant::now(), res.clone()));
+
+        res.map(|()| None)
+    }
+}
+
+async fn renderer(pool: &RenderPool) -> Result<Outcome, String> {
+    let Some(backend) = pool.backend() else {
+        // only builds without a rasterizer are meant to serve PDFs
+        return if cfg!(any(feature = "pdfium", feature = "hayro")) {
+            Err("no rasterizer could be loaded".to_owned())
+        } else {
+            Ok(Outcome::Ok(Some(
+                "none, floorplans are served as pdf".to_owned(),
+            )))
+        };
+    };
+
+    // pdfium is preferred, but hayro renders floorplans well enough
+    #[cfg(feature = "pdfium")]
+    let fallback = backend != crate::floorplan::Backend::Pdfium;
+    #[cfg(not(feature = "pdfium"))]
+    let fallback = false;
+    let outcome = |detail: &str| {
+        if fallback {
+            Outcome::Degraded(format!("{detail} (pdfium unavailable)"))
+        } else {
+            Outcome::Ok(Some(detail.to_owned()))
+        }
+    };
+
+    match pool.run(|rasterizer| rasterizer.is_some()).await {
+        Ok(true) => Ok(outcome(backend.name())),
+        Ok(false) => Err(format!("a worker failed to load {}", backend.name())),
+        // the workers are alive, just occupied
+        Err(PoolError::Busy) => Ok(outcome(&format!("{}, busy", backend.name()))),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+async fn db(db: &Db) -> Result<Option<String>, crate::db::DbError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    db.insert(HEALTH, "readyz", &now).await?;
+
+    Ok(None)
+}
+
+/// Check every dependency that is needed to serve requests.
+pub async fn readiness(state: &AppState) -> Readiness {
+    let (af, renderer, db) = tokio::join!(
+        Check::run(state.af_ping.check(&state.af)),
+        Check::run(renderer(&state.renderer)),
+        Check::run(db(&state.db)),
+    );
+    let checks = BTreeMap::from([("af", af), ("renderer", renderer), ("db", db)]);
+
+    Readiness {
+        ready: checks.values().all(Check::is_ok),
+        checks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{renderer, Outcome};
+    use crate::floorplan::RenderPool;
+
+    #[cfg(all(feature = "pdfium", feature = "hayro"))]
+    #[tokio::test]
+    async fn hayro_fallback() {
+        let pool = RenderPool::new(
+            Some(crate::floorplan::Backend::Hayro),
+            1,
+            1,
+            Duration::from_secs(1),
+        );
+
+        assert!(matches!(
+            renderer(&pool).await,
+            Ok(Outcome::Degraded(detail)) if detail == "hayro (pdfium unavailable)"
+        ));
+    }
+
+    #[tokio::test]
+    async fn no_rasterizer() {
+        let pool = RenderPool::new(None, 1, 1, Duration::from_secs(1));
+        let res = renderer(&pool).await;
+
+        if cfg!(any(feature = "pdfium", feature = "hayro")) {
+            assert!(res.is_err());
+        } else {
+            assert!(matches!(res, Ok(Outcome::Ok(Some(_)))));
+        }
+    }
+}
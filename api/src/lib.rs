@@ -4,18 +4,60 @@ use afbostader::Credentials;
 use axum::{
     async_trait,
     extract::{FromRef, FromRequestParts},
-    http::request::Parts,
+    http::{request::Parts, HeaderName},
 };
 use axum_extra::extract::{cookie::Key, PrivateCookieJar};
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
+pub mod calendar;
+pub mod commute;
+pub mod config;
+pub mod csrf;
+pub mod db;
+pub mod export;
+pub mod feed;
+pub mod filter;
 pub mod floorplan;
+pub mod geocode;
+pub mod graphql;
+pub mod health;
+pub mod limit;
+pub mod login;
+pub mod picture;
+pub mod routes;
+pub mod telemetry;
+#[cfg(test)]
+mod testing;
+pub mod v1;
+pub mod vacancy;
 
 #[derive(Clone)]
 pub struct AppState {
     pub af: afbostader::Client,
-    pub client: reqwest::Client,
+    pub db: db::Db,
+    pub geocoder: geocode::GeocodeCache,
+    pub commutes: commute::Commutes,
     pub key: Key,
+    pub cookies: config::CookieSettings,
+    pub pictures: picture::Pictures,
+    pub renderer: floorplan::RenderPool,
+    /// The last time `/readyz` pinged AF.
+    pub af_ping: health::AfPing,
+    pub login: login::LoginLimiter,
+    /// Limits how many calendars each client can save.
+    pub calendars: limit::IpLimiter,
+    /// Limits how many addresses that AF doesn't list each client can
+    /// geocode.
+    pub ad_hoc_geocodes: limit::IpLimiter,
+    /// Limits how many exports with vacancy details each client can make.
+    pub detailed_exports: limit::IpLimiter,
+    /// Where a trusted reverse proxy puts the client's IP address.
+    pub client_ip_header: Option<HeaderName>,
+    pub graphql: graphql::ApiSchema,
+    /// Where the frontend is served, for linking to it.
+    pub frontend_url: Url,
 }
 
 impl FromRef<AppState> for Key {
@@ -34,12 +76,18 @@ impl Deref for PersonalAf {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct EmailPassword {
     email: String,
     password: String,
 }
 
+impl EmailPassword {
+    pub fn email(&self) -> &str {
+        &self.email
+    }
+}
+
 impl From<EmailPassword> for Credentials {
     fn from(EmailPassword { email, password }: EmailPassword) -> Self {
         Self::new(email, password)
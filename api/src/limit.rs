@@ -0,0 +1,114 @@
+//! Per-IP limits for endpoints that anybody can use to make us do work.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    net::{IpAddr, Ipv6Addr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// What clients are told apart by: IPv4 addresses, and the /64 prefix of
+/// IPv6 addresses, since anybody with one IPv6 address has a whole /64 of
+/// them to rotate through.
+pub fn client_key(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => ip,
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => IpAddr::V6(Ipv6Addr::from(u128::from(v6) & !(u128::MAX >> 64))),
+        },
+    }
+}
+
+/// Allows each client, as told apart by [`client_key`], `limit` requests
+/// per `window`.
+#[derive(Debug, Clone)]
+pub struct IpLimiter {
+    recent: Arc<Mutex<HashMap<IpAddr, VecDeque<Instant>>>>,
+    limit: usize,
+    window: Duration,
+}
+
+impl IpLimiter {
+    pub fn new(limit: usize, window: Duration) -> Self {
+        Self {
+            recent: Default::default(),
+            limit,
+            window,
+        }
+    }
+
+    /// Register a request, or return how long to wait before trying again.
+    pub fn attempt(&self, ip: IpAddr) -> Result<(), Duration> {
+        self.attempt_at(Instant::now(), ip)
+    }
+
+    fn attempt_at(&self, now: Instant, ip: IpAddr) -> Result<(), Duration> {
+        let mut recent = self.recent.lock().unwrap();
+
+        recent.retain(|_, times| {
+            while times
+                .front()
+                .is_some_and(|&t| now.duration_since(t) >= self.window)
+            {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+
+        let times = recent.entry(client_key(ip)).or_default();
+        if times.len() >= self.limit {
+            return Err(times[0] + self.window - now);
+        }
+        times.push_back(now);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        time::{Duration, Instant},
+    };
+
+    use super::{client_key, IpLimiter};
+
+    #[test]
+    fn limits() {
+        let limiter = IpLimiter::new(10, Duration::from_secs(60 * 60));
+        let now = Instant::now();
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        for _ in 0..10 {
+            limiter.attempt_at(now, ip).unwrap();
+        }
+        assert_eq!(
+            limiter.attempt_at(now, ip),
+            Err(Duration::from_secs(60 * 60))
+        );
+        assert!(limiter
+            .attempt_at(now, IpAddr::V4(Ipv4Addr::BROADCAST))
+            .is_ok());
+        assert!(limiter
+            .attempt_at(now + Duration::from_secs(60 * 60), ip)
+            .is_ok());
+    }
+
+    #[test]
+    fn ipv6_prefixes() {
+        let limiter = IpLimiter::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        // the same /64 shares a budget
+        limiter.attempt_at(now, ip("2001:db8:0:1::1")).unwrap();
+        limiter.attempt_at(now, ip("2001:db8:0:1:ffff::2")).unwrap();
+        assert!(limiter.attempt_at(now, ip("2001:db8:0:1::3")).is_err());
+        assert!(limiter.attempt_at(now, ip("2001:db8:0:2::1")).is_ok());
+
+        assert_eq!(client_key(ip("::ffff:10.0.0.1")), ip("10.0.0.1"));
+        assert_eq!(client_key(ip("10.0.0.1")), ip("10.0.0.1"));
+    }
+}
@@ -0,0 +1,327 @@
+//! Throttling of login attempts.
+//!
+//! Every login is forwarded to AF, so without limits we would be a
+//! convenient proxy for credential stuffing, and AF would eventually block
+//! our IP address rather than the attacker's.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::request::Parts,
+};
+
+use crate::{limit::client_key, AppState};
+
+/// Consecutive failures before an address or email is locked out.
+const LOCKOUT_THRESHOLD: u32 = 3;
+
+/// The first lockout, which doubles with every further failure.
+const BASE_LOCKOUT: Duration = Duration::from_secs(30);
+
+const MAX_LOCKOUT: Duration = Duration::from_secs(60 * 60);
+
+/// How long failures are remembered, so that waiting out a lockout does
+/// not reset it.
+const FAILURE_MEMORY: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy)]
+pub struct LoginLimits {
+    /// Attempts per IP address and window.
+    pub per_ip: usize,
+    /// Attempts per email address and window.
+    pub per_email: usize,
+    pub window: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    BadCredentials,
+    /// Something other than the credentials went wrong, which says nothing
+    /// about whether they were correct.
+    Error,
+}
+
+#[derive(Debug, Default)]
+struct Attempts {
+    recent: VecDeque<Instant>,
+    failures: u32,
+    last_failure: Option<Instant>,
+    locked_until: Option<Instant>,
+}
+
+impl Attempts {
+    /// When the next attempt is allowed, if not now.
+    fn retry_at(&mut self, now: Instant, limit: usize, window: Duration) -> Option<Instant> {
+        while self
+            .recent
+            .front()
+            .is_some_and(|&t| now.duration_since(t) >= window)
+        {
+            self.recent.pop_front();
+        }
+
+        let locked = self.locked_until.filter(|&t| t > now);
+        let limited = (self.recent.len() >= limit).then(|| self.recent[0] + window);
+
+        locked.max(limited)
+    }
+
+    fn record(&mut self, now: Instant, outcome: Outcome) {
+        match outcome {
+            Outcome::Success => {
+                self.failures = 0;
+                self.last_failure = None;
+                self.locked_until = None;
+            }
+            Outcome::BadCredentials => {
+                self.failures += 1;
+                self.last_failure = Some(now);
+
+                if let Some(n) = self.failures.checked_sub(LOCKOUT_THRESHOLD) {
+                    let lockout = BASE_LOCKOUT
+                        .checked_mul(1 << n.min(16))
+                        .map_or(MAX_LOCKOUT, |d| d.min(MAX_LOCKOUT));
+                    self.locked_until = Some(now + lockout);
+                }
+            }
+            Outcome::Error => {}
+        }
+    }
+
+    /// Whether there is nothing worth remembering.
+    fn is_stale(&self, now: Instant, window: Duration) -> bool {
+        self.recent
+            .back()
+            .is_none_or(|&t| now.duration_since(t) >= window)
+            && self
+                .last_failure
+                .is_none_or(|t| now.duration_since(t) >= FAILURE_MEMORY)
+    }
+}
+
+#[derive(Debug)]
+struct Tracker<K> {
+    attempts: HashMap<K, Attempts>,
+}
+
+impl<K> Default for Tracker<K> {
+    fn default() -> Self {
+        Self {
+            attempts: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash> Tracker<K> {
+    fn get(&mut self, key: K) -> &mut Attempts {
+        self.attempts.entry(key).or_default()
+    }
+
+    fn prune(&mut self, now: Instant, window: Duration) {
+        self.attempts.retain(|_, a| !a.is_stale(now, window));
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// By [`client_key`], so that IPv6 clients can't rotate addresses.
+    ips: Tracker<IpAddr>,
+    emails: Tracker<String>,
+}
+
+/// Limits the number of login attempts per IP and email address, and locks
+/// them out for exponentially longer after repeated bad credentials.
+#[derive(Debug, Clone)]
+pub struct LoginLimiter {
+    state: Arc<Mutex<State>>,
+    limits: LoginLimits,
+}
+
+fn normalize(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+impl LoginLimiter {
+    pub fn new(limits: LoginLimits) -> Self {
+        Self {
+            state: Default::default(),
+            limits,
+        }
+    }
+
+    /// Register an attempt, or return how long to wait before trying again.
+    pub fn attempt(&self, ip: IpAddr, email: &str) -> Result<(), Duration> {
+        self.attempt_at(Instant::now(), ip, email)
+    }
+
+    fn attempt_at(&self, now: Instant, ip: IpAddr, email: &str) -> Result<(), Duration> {
+        let LoginLimits {
+            per_ip,
+            per_email,
+            window,
+        } = self.limits;
+        let mut state = self.state.lock().unwrap();
+        state.ips.prune(now, window);
+        state.emails.prune(now, window);
+
+        let ip = client_key(ip);
+        let retry_at = state.ips.get(ip).retry_at(now, per_ip, window).max(
+            state
+                .emails
+                .get(normalize(email))
+                .retry_at(now, per_email, window),
+        );
+
+        if let Some(t) = retry_at {
+            return Err(t - now);
+        }
+
+        state.ips.get(ip).recent.push_back(now);
+        state.emails.get(normalize(email)).recent.push_back(now);
+
+        Ok(())
+    }
+
+    /// Register the outcome of an attempt.
+    pub fn record(&self, ip: IpAddr, email: &str, outcome: Outcome) {
+        self.record_at(Instant::now(), ip, email, outcome)
+    }
+
+    fn record_at(&self, now: Instant, ip: IpAddr, email: &str, outcome: Outcome) {
+        let mut state = self.state.lock().unwrap();
+        state.ips.get(client_key(ip)).record(now, outcome);
+        state.emails.get(normalize(email)).record(now, outcome);
+    }
+}
+
+/// The IP address of the client, taken from a header set by a trusted
+/// reverse proxy if configured, or else from the connection.
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let from_header = state
+            .client_ip_header
+            .as_ref()
+            .and_then(|name| parts.headers.get(name))
+            .and_then(|v| v.to_str().ok())
+            // e.g. X-Forwarded-For lists the client first
+            .and_then(|v| v.split(',').next())
+            .and_then(|v| v.trim().parse().ok());
+        let from_connection = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        Ok(Self(
+            from_header
+                .or(from_connection)
+                .unwrap_or(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{IpAddr, Ipv4Addr, Ipv6Addr},
+        time::{Duration, Instant},
+    };
+
+    use super::{LoginLimiter, LoginLimits, Outcome};
+
+    const IP: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    fn limiter() -> LoginLimiter {
+        LoginLimiter::new(LoginLimits {
+            per_ip: 5,
+            per_email: 3,
+            window: Duration::from_secs(60),
+        })
+    }
+
+    #[test]
+    fn rate_limits() {
+        let limiter = limiter();
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            limiter.attempt_at(now, IP, "a@example.com").unwrap();
+        }
+        assert_eq!(
+            limiter.attempt_at(now, IP, " A@example.com"),
+            Err(Duration::from_secs(60))
+        );
+
+        // the email is used up, and then the address
+        limiter.attempt_at(now, IP, "b@example.com").unwrap();
+        limiter.attempt_at(now, IP, "c@example.com").unwrap();
+        assert!(limiter.attempt_at(now, IP, "d@example.com").is_err());
+
+        let later = now + Duration::from_secs(60);
+        assert!(limiter.attempt_at(later, IP, "a@example.com").is_ok());
+    }
+
+    #[test]
+    fn ipv6_prefixes() {
+        let limiter = limiter();
+        let now = Instant::now();
+
+        // rotating through a /64 doesn't give more attempts
+        for i in 0..5u16 {
+            let ip = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, i));
+            limiter
+                .attempt_at(now, ip, &format!("{i}@example.com"))
+                .unwrap();
+        }
+        let ip = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, 99));
+        assert!(limiter.attempt_at(now, ip, "x@example.com").is_err());
+    }
+
+    #[test]
+    fn lockout() {
+        let limiter = limiter();
+        let now = Instant::now();
+        let ip = |i| IpAddr::V4(Ipv4Addr::new(10, 0, 0, i));
+
+        for i in 0..3 {
+            let t = now + Duration::from_secs(60 * i as u64);
+            limiter.attempt_at(t, ip(i), "a@example.com").unwrap();
+            limiter.record_at(t, ip(i), "a@example.com", Outcome::BadCredentials);
+        }
+
+        let t = now + Duration::from_secs(120);
+        assert_eq!(
+            limiter.attempt_at(t, ip(9), "a@example.com"),
+            Err(Duration::from_secs(30))
+        );
+
+        let t = t + Duration::from_secs(30);
+        limiter.attempt_at(t, ip(9), "a@example.com").unwrap();
+        limiter.record_at(t, ip(9), "a@example.com", Outcome::BadCredentials);
+        assert_eq!(
+            limiter.attempt_at(t, ip(10), "a@example.com"),
+            Err(Duration::from_secs(60))
+        );
+
+        let t = t + Duration::from_secs(60);
+        limiter.attempt_at(t, ip(10), "a@example.com").unwrap();
+        limiter.record_at(t, ip(10), "a@example.com", Outcome::Success);
+        assert!(limiter.attempt_at(t, ip(11), "a@example.com").is_ok());
+    }
+}
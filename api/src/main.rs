@@ -1,125 +1,278 @@
-use std::{io::Cursor, net::SocketAddr, time::Duration};
+use std::{
+    future::ready,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
 
-use afbostader::PropertyId;
+use afbostader::{Area, PropertyId, TlsConfig};
 use amcoff_bostader_api::{
-    floorplan::{self, ToImageError},
+    calendar,
+    commute::{Commutes, Estimate, Mode, Osrm, RouteProvider},
+    config::{Config, GeocoderKind},
+    csrf::{self, AllowedOrigins},
+    db::Db,
+    export, feed,
+    filter::VacancyFilter,
+    floorplan::Room,
+    floorplan::{self, Backend, Floorplan, PoolError, RenderPool, ToImageError},
+    geocode::{
+        self, Gazetteer, GeocodeCache, GeocodeError, GeocodeQuery, Geocoder, Nominatim,
+        NominatimPlace, Photon, Place,
+    },
+    graphql,
+    health::{self, Readiness},
+    limit::IpLimiter,
+    login::{self, ClientIp, LoginLimiter},
+    picture::{self, PictureError, Pictures},
+    routes::{self, AfError},
+    telemetry, v1,
+    vacancy::Vacancy,
     AppState, EmailPassword, PersonalAf,
 };
 use axum::{
-    error_handling::HandleErrorLayer,
     extract::{Path, Query, State},
+    middleware,
     response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
 use axum_extra::{
-    extract::{
-        cookie::{Cookie, Key, SameSite},
-        PrivateCookieJar,
-    },
+    extract::{cookie::Key, PrivateCookieJar},
     TypedHeader,
 };
-use clap::Parser;
 use headers::{CacheControl, ContentType};
-use image::ImageFormat;
-use reqwest::StatusCode;
-use serde::{Deserialize, Serialize};
+use reqwest::{header, Method, StatusCode};
+use serde::Deserialize;
+use time::OffsetDateTime;
 use tokio::net::TcpListener;
-use tower::{buffer::BufferLayer, limit::RateLimitLayer, BoxError, ServiceBuilder};
-use tower_http::cors::CorsLayer;
-use tracing::{error, info};
+use tower_http::{cors::CorsLayer, trace::TraceLayer};
+use tracing::{debug, error, info, warn, Span};
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, SecurityScheme},
+    IntoParams, Modify, OpenApi,
+};
+use utoipa_swagger_ui::SwaggerUi;
+
+/// Addresses that are not in the cache are geocoded at a rate of one per
+/// second, so there is no point in accepting huge batches.
+const MAX_GEOCODE_BATCH_SIZE: usize = 100;
+
+/// Addresses that AF doesn't list that one IP address can geocode per hour.
+const AD_HOC_GEOCODES_PER_HOUR: usize = 30;
+
+/// Geocode `query`, limiting how many addresses that AF doesn't list each
+/// client can look up.
+async fn geocode_place(
+    state: &AppState,
+    ip: IpAddr,
+    query: &GeocodeQuery,
+) -> Result<Option<Place>, GeocodeError> {
+    if state.geocoder.is_ad_hoc(query).await? {
+        state
+            .ad_hoc_geocodes
+            .attempt(ip)
+            .map_err(GeocodeError::RateLimited)?;
+    }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct GeocodeQuery {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    street: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    city: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    postalcode: Option<String>,
+    state.geocoder.lookup(query, true).await
 }
 
-#[derive(Debug, thiserror::Error)]
-enum GeocodeError {
-    #[error(transparent)]
-    Http(#[from] reqwest::Error),
+fn geocode_cache_control() -> TypedHeader<CacheControl> {
+    TypedHeader(
+        CacheControl::new()
+            .with_public()
+            .with_max_age(Duration::from_secs(86_400)),
+    )
 }
 
-impl IntoResponse for GeocodeError {
-    fn into_response(self) -> Response {
-        (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
-    }
+/// Geocode an address. Addresses that AF lists are cached; others are
+/// limited per client and not stored.
+///
+/// Unlike the unversioned `/geocode`, which still gives the coordinates as
+/// strings like Nominatim, this is a list of numeric `{lat, lon}`.
+#[utoipa::path(
+    get,
+    path = "/v1/geocode",
+    tag = "geocode",
+    params(GeocodeQuery),
+    responses(
+        (status = 200, description = "At most one place, as a list", body = Vec<Place>),
+        (status = 429, description = "Too many addresses that AF doesn't list"),
+        (status = 503, description = "Too many addresses are being geocoded"),
+    ),
+)]
+async fn geocode(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    Query(query): Query<GeocodeQuery>,
+) -> Result<impl IntoResponse, GeocodeError> {
+    let place = geocode_place(&state, ip, &query).await?;
+
+    // a list, to stay compatible with nominatim responses
+    Ok((geocode_cache_control(), Json(Vec::from_iter(place))))
 }
 
-async fn geocode(
+/// The unversioned `/geocode`, in the shape of Nominatim's response that it
+/// used to pass on.
+async fn legacy_geocode(
     State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
     Query(query): Query<GeocodeQuery>,
 ) -> Result<impl IntoResponse, GeocodeError> {
-    let res = state
-        .client
-        .get("https://nominatim.openstreetmap.org/search.php?format=jsonv2")
-        .query(&query)
-        .send()
-        .await?;
-    Ok(([("content-type", "application/json")], res.text().await?))
+    let place = geocode_place(&state, ip, &query).await?;
+    let places: Vec<_> = place.into_iter().map(NominatimPlace::from).collect();
+
+    Ok((geocode_cache_control(), Json(places)))
 }
 
-#[derive(Debug, thiserror::Error)]
-#[error(transparent)]
-struct AfError(#[from] afbostader::Error);
-
-impl AfError {
-    fn status_code(&self) -> StatusCode {
-        use afbostader::Error;
-
-        match self.0 {
-            Error::Http(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            Error::BadCredentials => StatusCode::FORBIDDEN,
-            Error::Unauthenticated => StatusCode::UNAUTHORIZED,
-            Error::Unknown(_) => StatusCode::INTERNAL_SERVER_ERROR,
-        }
+/// Geocode the addresses of vacancies. Addresses that AF doesn't list are
+/// not looked up, and come back as `null`.
+#[utoipa::path(
+    post,
+    path = "/v1/geocode/batch",
+    tag = "geocode",
+    request_body = Vec<GeocodeQuery>,
+    responses(
+        (status = 200, description = "The place of each address, in order", body = Vec<Option<Place>>),
+        (status = 413, description = "Too many addresses"),
+        (status = 503, description = "Too many addresses are being geocoded"),
+    ),
+)]
+async fn geocode_batch(
+    State(state): State<AppState>,
+    Json(queries): Json<Vec<GeocodeQuery>>,
+) -> Response {
+    if queries.len() > MAX_GEOCODE_BATCH_SIZE {
+        return (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("at most {MAX_GEOCODE_BATCH_SIZE} addresses per batch"),
+        )
+            .into_response();
     }
-}
 
-impl IntoResponse for AfError {
-    fn into_response(self) -> Response {
-        (self.status_code(), self.to_string()).into_response()
+    let mut places = Vec::with_capacity(queries.len());
+    for query in &queries {
+        places.push(match state.geocoder.lookup(query, false).await {
+            Ok(place) => place,
+            Err(e @ GeocodeError::Busy) => return e.into_response(),
+            Err(e) => {
+                warn!("failed to geocode {query:?}: {e}");
+                None
+            }
+        });
     }
+
+    Json(places).into_response()
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct VacancyQuery {
+    /// Sort by the distance to this point of interest.
+    sort_by: Option<String>,
+    /// Sort by travel time rather than straight-line distance.
+    mode: Option<Mode>,
 }
 
-async fn list_vacancies(af: PersonalAf) -> Result<impl IntoResponse, AfError> {
+#[utoipa::path(
+    get,
+    path = "/v1/vacancies",
+    tag = "vacancies",
+    params(VacancyQuery, VacancyFilter),
+    security((), ("login" = [])),
+    responses(
+        (status = 200, body = Vec<v1::Vacancy>),
+        (status = 400, description = "Unknown point of interest"),
+    ),
+)]
+async fn list_vacancies(
+    State(state): State<AppState>,
+    af: PersonalAf,
+    Query(query): Query<VacancyQuery>,
+    Query(filter): Query<VacancyFilter>,
+) -> Result<Response, AfError> {
+    let poi = match query.sort_by {
+        Some(name) => match state
+            .commutes
+            .points_of_interest()
+            .iter()
+            .position(|poi| poi.name.eq_ignore_ascii_case(&name))
+        {
+            Some(i) => Some(i),
+            None => {
+                return Ok((StatusCode::BAD_REQUEST, "unknown point of interest").into_response())
+            }
+        },
+        None => None,
+    };
+
+    let properties = af.list_vacancies().await?;
+    let mut vacancies =
+        Vacancy::many(properties.into_iter().filter(|p| filter.matches(p)), &state).await;
+
+    if let Some(i) = poi {
+        // vacancies without a known location end up last
+        vacancies.sort_by(|a, b| {
+            let key = |v: &Vacancy<_>| {
+                v.commutes
+                    .get(i)
+                    .and_then(|c| c.sort_key(query.mode))
+                    .unwrap_or(f64::INFINITY)
+            };
+            key(a).total_cmp(&key(b))
+        });
+    }
+
     Ok((
         TypedHeader(CacheControl::new().with_private()),
-        Json(af.list_vacancies().await?),
-    ))
+        Json(Vec::from_iter(vacancies.into_iter().map(v1::Vacancy::from))),
+    )
+        .into_response())
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/vacancies/{id}",
+    tag = "vacancies",
+    params(("id" = u32, Path)),
+    security((), ("login" = [])),
+    responses((status = 200, body = v1::VacancyDetail)),
+)]
 async fn get_vacancy_detail(
+    State(state): State<AppState>,
     af: PersonalAf,
     Path(id): Path<PropertyId>,
 ) -> Result<impl IntoResponse, AfError> {
+    let detail = af.0.vacancy_detail(id).await?;
+
     Ok((
         TypedHeader(CacheControl::new().with_private()),
-        Json(af.0.vacancy_detail(id).await?),
+        Json(v1::VacancyDetail::from(Vacancy::new(detail, &state).await)),
     ))
 }
 
+fn to_image_status_code(e: &ToImageError) -> StatusCode {
+    match e {
+        ToImageError::Pool(PoolError::Busy) => StatusCode::SERVICE_UNAVAILABLE,
+        ToImageError::Pool(PoolError::Timeout) => StatusCode::GATEWAY_TIMEOUT,
+        ToImageError::NoBackend => StatusCode::NOT_IMPLEMENTED,
+        ToImageError::TooLarge(_) | ToImageError::ContentType(_) => StatusCode::BAD_GATEWAY,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 enum FloorplanError {
-    #[error("http error: {0}")]
-    Http(#[from] reqwest_middleware::Error),
     #[error(transparent)]
     ToImageError(#[from] ToImageError),
     #[error(transparent)]
     Af(AfError),
 }
 
-impl<T> From<T> for FloorplanError
-where
-    AfError: From<T>,
-{
-    fn from(value: T) -> Self {
-        Self::Af(AfError::from(value))
+impl From<afbostader::Error> for FloorplanError {
+    fn from(value: afbostader::Error) -> Self {
+        Self::Af(value.into())
     }
 }
 
@@ -127,35 +280,61 @@ impl IntoResponse for FloorplanError {
     fn into_response(self) -> Response {
         error!("Error: {:?}", self);
 
-        (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+        let status = match self {
+            Self::ToImageError(ref e) => to_image_status_code(e),
+            Self::Af(ref e) => e.status_code(),
+        };
+
+        (status, self.to_string()).into_response()
     }
 }
 
+/// Download the floorplan of a vacancy, if it has one.
+async fn fetch_floorplan(
+    af: &PersonalAf,
+    id: PropertyId,
+) -> Result<Option<reqwest::Response>, FloorplanError> {
+    let Some(url) = af.vacancy_detail(id).await?.blueprint else {
+        return Ok(None);
+    };
+
+    Ok(Some(af.download(url).await?))
+}
+
+/// The floorplan of a vacancy.
+#[utoipa::path(
+    get,
+    path = "/v1/vacancies/{id}/floorplan",
+    tag = "vacancies",
+    params(("id" = u32, Path)),
+    security((), ("login" = [])),
+    responses(
+        (status = 200, description = "A PNG, or a PDF if it cannot be rendered", content(("image/png"), ("application/pdf"))),
+        (status = 204, description = "The vacancy has no floorplan"),
+    ),
+)]
 async fn get_vacancy_floorplan(
     State(state): State<AppState>,
+    af: PersonalAf,
     Path(id): Path<PropertyId>,
 ) -> Result<Response, FloorplanError> {
-    let Some(url) = state
-        .af
-        .vacancy_detail(id)
-        .await
-        .map_err(AfError)?
-        .blueprint
-    else {
+    let Some(res) = fetch_floorplan(&af, id).await? else {
         return Ok(StatusCode::NO_CONTENT.into_response());
     };
 
-    let res = state.af.inner().get(url).send().await?;
-
-    let img = floorplan::to_image(res).await?;
+    let img = match floorplan::to_image(res, &state.renderer).await? {
+        Floorplan::Image(img) => img,
+        Floorplan::Pdf(pdf) => {
+            return Ok((
+                [(header::CONTENT_TYPE, "application/pdf")],
+                TypedHeader(CacheControl::new().with_max_age(Duration::from_secs(86_400))),
+                pdf,
+            )
+                .into_response())
+        }
+    };
 
-    let png = tokio::task::spawn_blocking(move || {
-        let mut out = Cursor::new(Vec::new());
-        img.write_to(&mut out, ImageFormat::Png).unwrap();
-        out.into_inner()
-    })
-    .await
-    .unwrap();
+    let png = floorplan::to_png(img).await?;
 
     Ok((
         TypedHeader(ContentType::png()),
@@ -165,6 +344,76 @@ async fn get_vacancy_floorplan(
         .into_response())
 }
 
+/// The rooms labelled on the floorplan of a vacancy.
+#[utoipa::path(
+    get,
+    path = "/v1/vacancies/{id}/floorplan/rooms",
+    tag = "vacancies",
+    params(("id" = u32, Path)),
+    security((), ("login" = [])),
+    responses(
+        (status = 200, body = Vec<Room>),
+        (status = 204, description = "The vacancy has no floorplan"),
+    ),
+)]
+async fn get_vacancy_floorplan_rooms(
+    State(state): State<AppState>,
+    af: PersonalAf,
+    Path(id): Path<PropertyId>,
+) -> Result<Response, FloorplanError> {
+    let Some(res) = fetch_floorplan(&af, id).await? else {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    };
+
+    let rooms = floorplan::rooms(res, &state.renderer).await?;
+
+    Ok((
+        TypedHeader(CacheControl::new().with_max_age(Duration::from_secs(86_400))),
+        Json(rooms),
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/areas",
+    tag = "areas",
+    security((), ("login" = [])),
+    responses((status = 200, body = Vec<v1::AreaSummary>)),
+)]
+async fn list_areas(af: PersonalAf) -> Result<impl IntoResponse, AfError> {
+    let (areas, vacancies) = tokio::try_join!(af.0.list_areas(), af.0.list_vacancies())?;
+    let mut areas: Vec<_> = areas.into_iter().map(|area| (area, 0)).collect();
+
+    for vacancy in vacancies {
+        match areas.iter_mut().find(|(a, _)| a.matches(&vacancy.area)) {
+            Some((_, n)) => *n += 1,
+            // not every area with vacancies has a page of its own
+            None => areas.push((Area::from_name(&vacancy.area), 1)),
+        }
+    }
+
+    let areas: Vec<_> = areas
+        .into_iter()
+        .map(|(area, vacancies)| v1::AreaSummary {
+            area: area.into(),
+            vacancies,
+        })
+        .collect();
+
+    Ok((TypedHeader(CacheControl::new().with_private()), Json(areas)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/areas/{name}",
+    tag = "areas",
+    params(("name" = String, Path)),
+    responses(
+        (status = 200, body = v1::AreaDetail),
+        (status = 404, description = "No such area"),
+    ),
+)]
 async fn get_area_detail(
     af: PersonalAf,
     Path(name): Path<String>,
@@ -176,99 +425,485 @@ async fn get_area_detail(
                 .with_public()
                 .with_max_age(Duration::from_secs(3600)),
         ),
-        Json(detail),
+        Json(v1::AreaDetail::from(detail)),
     ))
 }
 
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PictureQuery {
+    /// The largest width or height wanted, in pixels.
+    size: Option<u32>,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum AreaPictureError {
+    #[error("no such picture")]
+    NotFound,
+    #[error(transparent)]
+    Af(#[from] AfError),
+    #[error(transparent)]
+    Picture(PictureError),
+}
+
+impl From<afbostader::Error> for AreaPictureError {
+    fn from(value: afbostader::Error) -> Self {
+        Self::Af(value.into())
+    }
+}
+
+impl From<PictureError> for AreaPictureError {
+    fn from(value: PictureError) -> Self {
+        match value {
+            PictureError::Af(e) => e.into(),
+            e => Self::Picture(e),
+        }
+    }
+}
+
+impl IntoResponse for AreaPictureError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Af(ref e) => e.status_code(),
+            Self::Picture(PictureError::Download(ref e)) => to_image_status_code(e),
+            Self::Picture(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        if status.is_server_error() {
+            error!("Error: {:?}", self);
+        }
+
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// A picture of an area, resized and served from our own origin.
+#[utoipa::path(
+    get,
+    path = "/v1/areas/{name}/pictures/{idx}",
+    tag = "areas",
+    params(("name" = String, Path), ("idx" = usize, Path), PictureQuery),
+    responses(
+        (status = 200, content_type = "image/jpeg"),
+        (status = 404, description = "No such area or picture"),
+    ),
+)]
+async fn get_area_picture(
+    State(state): State<AppState>,
+    af: PersonalAf,
+    Path((name, idx)): Path<(String, usize)>,
+    Query(query): Query<PictureQuery>,
+) -> Result<Response, AreaPictureError> {
+    let url = state
+        .pictures
+        .area_urls(&af, &name)
+        .await?
+        .into_iter()
+        .nth(idx)
+        .ok_or(AreaPictureError::NotFound)?;
+    let jpeg = state
+        .pictures
+        .get(&af, url, picture::size(query.size))
+        .await?;
+
+    Ok((
+        TypedHeader(ContentType::jpeg()),
+        TypedHeader(
+            CacheControl::new()
+                .with_public()
+                .with_max_age(Duration::from_secs(86_400)),
+        ),
+        jpeg,
+    )
+        .into_response())
+}
+
+/// The process is up.
+#[utoipa::path(get, path = "/healthz", tag = "health", responses((status = 200)))]
+async fn healthz() -> impl IntoResponse {
+    Json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Everything needed to serve requests is available.
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    responses(
+        (status = 200, body = Readiness),
+        (status = 503, body = Readiness),
+    ),
+)]
+async fn readyz(State(state): State<AppState>) -> impl IntoResponse {
+    let readiness = health::readiness(&state).await;
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        warn!("not ready: {readiness:?}");
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        TypedHeader(CacheControl::new().with_no_store()),
+        Json(readiness),
+    )
+}
+
+#[derive(Debug, thiserror::Error)]
+enum LoginError {
+    #[error("too many login attempts")]
+    RateLimited(Duration),
+    #[error(transparent)]
+    Af(#[from] AfError),
+}
+
+impl IntoResponse for LoginError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::RateLimited(retry_after) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                // round up, so that clients do not retry just before
+                [(header::RETRY_AFTER, (retry_after.as_secs() + 1).to_string())],
+                self.to_string(),
+            )
+                .into_response(),
+            Self::Af(e) => e.into_response(),
+        }
+    }
+}
+
+/// Check the credentials with AF and store them in an encrypted cookie.
+#[utoipa::path(
+    post,
+    path = "/v1/login",
+    tag = "user",
+    request_body = EmailPassword,
+    responses(
+        (status = 200, body = v1::User),
+        (status = 403, description = "Bad credentials"),
+        (status = 429, description = "Too many attempts", headers(("retry-after" = u64))),
+    ),
+)]
 async fn login(
     State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
     jar: PrivateCookieJar,
     Json(details): Json<EmailPassword>,
-) -> Result<impl IntoResponse, AfError> {
-    let cookie = Cookie::build(("login", serde_json::to_string(&details).unwrap()))
-        .http_only(true)
-        .path("/")
-        .permanent()
-        .same_site(SameSite::None)
-        .build();
-
-    let user = state
-        .af
-        .with_credentials(details.into())
-        .user_info()
-        .await?;
+) -> Result<impl IntoResponse, LoginError> {
+    let email = details.email().to_owned();
+
+    if let Err(retry_after) = state.login.attempt(ip, &email) {
+        info!(target: "audit", %ip, email, outcome = "rate-limited", ?retry_after, "login");
+        return Err(LoginError::RateLimited(retry_after));
+    }
+
+    let cookie = state
+        .cookies
+        .build("login", serde_json::to_string(&details).unwrap());
+
+    let result = state.af.with_credentials(details.into()).user_info().await;
+    let outcome = match result {
+        Ok(_) => login::Outcome::Success,
+        Err(afbostader::Error::BadCredentials) => login::Outcome::BadCredentials,
+        Err(_) => login::Outcome::Error,
+    };
+    state.login.record(ip, &email, outcome);
+    info!(target: "audit", %ip, email, ?outcome, "login");
 
-    Ok((jar.add(cookie), Json(user)))
+    Ok((
+        jar.add(cookie),
+        Json(v1::User::from(result.map_err(AfError)?)),
+    ))
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/user",
+    tag = "user",
+    security(("login" = [])),
+    responses(
+        (status = 200, body = v1::User),
+        (status = 401, description = "Not logged in"),
+    ),
+)]
 async fn user(af: PersonalAf) -> Result<impl IntoResponse, AfError> {
     Ok((
         TypedHeader(CacheControl::new().with_no_cache()),
-        Json(af.user_info().await?),
+        Json(v1::User::from(af.user_info().await?)),
     ))
 }
 
-async fn logout(jar: PrivateCookieJar) -> impl IntoResponse {
-    jar.remove("login")
+#[utoipa::path(post, path = "/v1/logout", tag = "user", responses((status = 200)))]
+async fn logout(State(state): State<AppState>, jar: PrivateCookieJar) -> impl IntoResponse {
+    jar.remove(state.cookies.removal("login"))
 }
 
-#[derive(Debug, Parser)]
-struct Args {
-    #[clap(long, env)]
-    cookie_key: Option<String>,
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "A(mcof)F Bostäder"),
+    paths(
+        list_vacancies,
+        routes::export::export_csv,
+        routes::export::export_xlsx,
+        get_vacancy_detail,
+        get_vacancy_floorplan,
+        get_vacancy_floorplan_rooms,
+        list_areas,
+        get_area_detail,
+        get_area_picture,
+        login,
+        user,
+        logout,
+        geocode,
+        geocode_batch,
+        routes::calendar::get_calendar,
+        routes::calendar::save_calendar,
+        routes::calendar::get_saved_calendar,
+        routes::feed::get_atom_feed,
+        routes::feed::get_rss_feed,
+        healthz,
+        readyz,
+    ),
+    modifiers(&LoginCookie),
+)]
+struct ApiDoc;
+
+/// The encrypted cookie set by `/login`. Without it, vacancies are listed
+/// without queue positions.
+struct LoginCookie;
+
+impl Modify for LoginCookie {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "login",
+                SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new("login"))),
+            );
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let _ = dotenvy::dotenv();
-    tracing_subscriber::fmt::init();
 
-    let Args { cookie_key } = Args::parse();
+    let config = Config::load()?;
+
+    let _tracer = telemetry::init_tracing(config.log_format, config.otlp_endpoint.clone())?;
+    let metrics = telemetry::install()?;
 
-    let cookie_key = cookie_key
+    let cookie_key = config
+        .cookie_key
+        .as_ref()
         .map(|s| Key::from(s.as_bytes()))
         .unwrap_or_else(Key::generate);
 
-    let af = afbostader::Client::new();
-
-    let app = Router::new()
+    let af = afbostader::Client::with_config(
+        TlsConfig {
+            intermediates: match config.af_intermediates {
+                Some(ref path) => TlsConfig::parse_intermediates(&std::fs::read(path)?)?,
+                None => Vec::new(),
+            },
+            pins: config.af_cert_pins.clone(),
+        },
+        config.af_throttle(),
+    );
+    let db = Db::open(&config.db_path)?;
+    let client = reqwest::Client::builder()
+        .user_agent(afbostader::USER_AGENT)
+        .build()
+        .unwrap();
+    let router: Arc<dyn RouteProvider> = match (
+        config.osrm_walking_url.clone(),
+        config.osrm_cycling_url.clone(),
+    ) {
+        (Some(walking), Some(cycling)) => {
+            Arc::new(Osrm::new(client.clone(), db.clone(), walking, cycling))
+        }
+        _ => Arc::new(Estimate),
+    };
+    let provider: Arc<dyn Geocoder> = match config.geocoder {
+        GeocoderKind::Nominatim => Arc::new(
+            Nominatim::new(client, config.nominatim_url.clone())
+                .with_interval(config.geocode_interval()),
+        ),
+        GeocoderKind::Photon => Arc::new(
+            Photon::new(client, config.photon_url.clone()).with_interval(config.geocode_interval()),
+        ),
+        GeocoderKind::Gazetteer => {
+            let gazetteer = Gazetteer::open(config.gazetteer_path.as_ref().unwrap())?;
+            info!("loaded {} addresses into the gazetteer", gazetteer.len());
+            Arc::new(gazetteer)
+        }
+    };
+    let geocoder = GeocodeCache::new(provider, db.clone());
+
+    tokio::spawn({
+        let (geocoder, af, db) = (geocoder.clone(), af.clone(), db.clone());
+        let period = config.geocode_prewarm_interval();
+
+        async move {
+            let mut interval = tokio::time::interval(period);
+
+            loop {
+                interval.tick().await;
+
+                match calendar::prune(&db, OffsetDateTime::now_utc()).await {
+                    Ok(0) => {}
+                    Ok(n) => info!("removed {n} unused calendars"),
+                    Err(e) => warn!("failed to remove unused calendars: {e}"),
+                }
+
+                let vacancies = match af.list_vacancies().await {
+                    Ok(vacancies) => vacancies,
+                    Err(e) => {
+                        warn!("failed to list vacancies: {e}");
+                        continue;
+                    }
+                };
+
+                // so that feeds date new vacancies by when they appeared,
+                // not by when somebody next asked for a feed
+                let now = OffsetDateTime::now_utc();
+                if let Err(e) = feed::first_seen(&db, &vacancies, now).await {
+                    warn!("failed to record new vacancies: {e}");
+                }
+                match feed::prune(&db, &vacancies, now).await {
+                    Ok(0) => {}
+                    Ok(n) => info!("forgot {n} vacancies that are gone"),
+                    Err(e) => warn!("failed to forget vacancies that are gone: {e}"),
+                }
+                geocode::prewarm(&geocoder, &vacancies).await;
+            }
+        }
+    });
+
+    let origins = AllowedOrigins::new(config.cors_origins()?);
+    let cors = CorsLayer::new()
+        .allow_origin(origins.to_vec())
+        .allow_credentials(true)
+        .allow_methods([Method::GET, Method::POST])
+        .allow_headers([header::CONTENT_TYPE])
+        .max_age(Duration::from_secs(3600));
+
+    // the routes from before there were versions
+    let api = Router::new()
         .route("/vacancies", get(list_vacancies))
         .route("/vacancies/:id", get(get_vacancy_detail))
         .route("/vacancies/:id/floorplan", get(get_vacancy_floorplan))
+        .route(
+            "/vacancies/:id/floorplan/rooms",
+            get(get_vacancy_floorplan_rooms),
+        )
+        .route("/areas", get(list_areas))
         .route("/areas/:name", get(get_area_detail))
+        .route("/areas/:name/pictures/:idx", get(get_area_picture))
         .route("/login", post(login))
         .route("/user", get(user))
-        .route("/logout", get(logout))
+        .route("/logout", post(logout))
+        // its response has been the same since it was added
+        .route("/geocode/batch", post(geocode_batch));
+    let legacy = api.clone().route("/geocode", get(legacy_geocode));
+    let v1 = api
+        .route("/geocode", get(geocode))
+        .route("/vacancies.csv", get(routes::export::export_csv))
+        .route("/vacancies.xlsx", get(routes::export::export_xlsx))
+        .route("/calendar.ics", get(routes::calendar::get_calendar))
+        .route("/calendars", post(routes::calendar::save_calendar))
         .route(
-            "/geocode",
-            get(geocode).route_layer(
-                ServiceBuilder::new()
-                    .layer(HandleErrorLayer::new(|err: BoxError| async move {
-                        (
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            format!("Unhandled error: {}", err),
-                        )
-                    }))
-                    .layer(BufferLayer::new(1024))
-                    // The "absolute maximum" according to the Nominatim Usage Policy
-                    // is 1 request per second
-                    .layer(RateLimitLayer::new(1, Duration::from_secs(1))),
-            ),
+            "/calendars/:token",
+            get(routes::calendar::get_saved_calendar),
+        )
+        .route("/feed.atom", get(routes::feed::get_atom_feed))
+        .route("/feed.rss", get(routes::feed::get_rss_feed));
+
+    let app = Router::new()
+        .nest("/v1", v1)
+        .merge(legacy.layer(middleware::from_fn(v1::deprecated)))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/graphql", get(graphql::graphiql).post(graphql::handler))
+        .merge(SwaggerUi::new("/docs").url("/openapi.json", ApiDoc::openapi()))
+        .layer(middleware::from_fn(telemetry::track_requests))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(telemetry::request_span)
+                .on_response(|res: &Response, latency: Duration, span: &Span| {
+                    span.record("status", res.status().as_u16());
+                    debug!(?latency, "finished request");
+                }),
         )
-        .layer(CorsLayer::very_permissive())
+        .layer(middleware::from_fn_with_state(origins, csrf::verify_origin))
+        .layer(cors)
         .with_state(AppState {
-            client: reqwest::Client::builder()
-                .user_agent(afbostader::USER_AGENT)
-                .build()
-                .unwrap(),
+            geocoder,
+            commutes: Commutes::new(config.pois.clone(), router),
             af,
             key: cookie_key,
+            cookies: config.cookies(),
+            pictures: Pictures::new(db.clone()),
+            db,
+            renderer: RenderPool::new(
+                Backend::detect(),
+                config.render_workers,
+                config.render_queue_size,
+                Duration::from_secs(config.render_timeout),
+            ),
+            af_ping: Default::default(),
+            login: LoginLimiter::new(config.login_limits()),
+            calendars: IpLimiter::new(calendar::SAVES_PER_HOUR, Duration::from_secs(60 * 60)),
+            ad_hoc_geocodes: IpLimiter::new(AD_HOC_GEOCODES_PER_HOUR, Duration::from_secs(60 * 60)),
+            detailed_exports: IpLimiter::new(
+                export::DETAILED_EXPORTS_PER_HOUR,
+                Duration::from_secs(60 * 60),
+            ),
+            client_ip_header: config.client_ip_header.clone(),
+            graphql: graphql::schema(),
+            frontend_url: config.frontend_url.clone(),
         });
-    let addr: SocketAddr = "[::]:8000".parse().unwrap();
+    // on an address of its own, so that it isn't public
+    let metrics = Router::new().route("/metrics", get(move || ready(metrics.render())));
+    let listener = TcpListener::bind(config.metrics_bind).await?;
+    info!("Serving metrics on {}", config.metrics_bind);
+    tokio::spawn(async move { axum::serve(listener, metrics).await });
+
+    let addr = config.addr();
     let listener = TcpListener::bind(addr).await.unwrap();
     info!("Listening on {}", addr);
 
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use utoipa::OpenApi;
+
+    use super::ApiDoc;
+
+    /// The frontend's types are generated from `openapi.json`, so it must
+    /// be kept up to date. Run with `UPDATE_OPENAPI=1` to update it.
+    #[test]
+    fn openapi() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+        let json = ApiDoc::openapi().to_pretty_json().unwrap() + "\n";
+
+        if std::env::var_os("UPDATE_OPENAPI").is_some() {
+            std::fs::write(path, json).unwrap();
+        } else {
+            let committed = std::fs::read_to_string(path).unwrap_or_default();
+            assert!(
+                committed == json,
+                "{path} is out of date, run the tests with UPDATE_OPENAPI=1"
+            );
+        }
+    }
+}
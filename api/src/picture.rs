@@ -0,0 +1,159 @@
+//! Area pictures, proxied from the AF website.
+//!
+//! www.afbostader.se serves an incomplete certificate chain, which some
+//! browsers refuse, and the pictures are much larger than they are shown.
+
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use afbostader::Client;
+use axum::body::Bytes;
+use image::codecs::jpeg::JpegEncoder;
+use reqwest::Url;
+
+use crate::{
+    db::{Db, DbError, PICTURES},
+    floorplan::{self, ToImageError},
+};
+
+/// The sizes that pictures are resized to, so that the cache stays small.
+const SIZES: [u32; 4] = [250, 500, 1000, 2000];
+
+const JPEG_QUALITY: u8 = 80;
+
+/// Pictures larger than this (in bytes) are rejected.
+const MAX_DOWNLOAD_SIZE: usize = 10 * 1024 * 1024;
+
+/// How long the URLs of an area's pictures are remembered, so that the
+/// area page isn't scraped for every picture.
+const URLS_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// The smallest supported size that is at least `requested`.
+pub fn size(requested: Option<u32>) -> u32 {
+    let requested = requested.unwrap_or(1000);
+
+    SIZES
+        .into_iter()
+        .find(|&size| size >= requested)
+        .unwrap_or(SIZES[SIZES.len() - 1])
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PictureError {
+    #[error(transparent)]
+    Af(#[from] afbostader::Error),
+    #[error(transparent)]
+    Download(#[from] ToImageError),
+    #[error(transparent)]
+    Db(#[from] DbError),
+}
+
+impl From<image::ImageError> for PictureError {
+    fn from(value: image::ImageError) -> Self {
+        Self::Download(value.into())
+    }
+}
+
+struct AreaUrls {
+    fetched: Instant,
+    urls: Vec<Url>,
+}
+
+/// Resized pictures, cached in the database.
+#[derive(Clone)]
+pub struct Pictures {
+    db: Db,
+    /// The picture URLs of each area, by lowercase name.
+    urls: Arc<Mutex<HashMap<String, AreaUrls>>>,
+}
+
+impl Pictures {
+    pub fn new(db: Db) -> Self {
+        Self {
+            db,
+            urls: Default::default(),
+        }
+    }
+
+    /// The URLs of the pictures of the area called `name`.
+    pub async fn area_urls(&self, af: &Client, name: &str) -> Result<Vec<Url>, PictureError> {
+        let key = name.trim().to_lowercase();
+
+        if let Some(area) = self.urls.lock().unwrap().get(&key) {
+            if area.fetched.elapsed() < URLS_TTL {
+                return Ok(area.urls.clone());
+            }
+        }
+
+        let urls: Vec<_> = af
+            .area_detail(name)
+            .await?
+            .pictures
+            .into_iter()
+            .map(|picture| picture.url)
+            .collect();
+
+        let mut cache = self.urls.lock().unwrap();
+        cache.retain(|_, area| area.fetched.elapsed() < URLS_TTL);
+        cache.insert(
+            key,
+            AreaUrls {
+                fetched: Instant::now(),
+                urls: urls.clone(),
+            },
+        );
+
+        Ok(urls)
+    }
+
+    /// Get the picture at `url` as a JPEG that fits within `size`×`size`
+    /// pixels. Resizing doesn't need a rasterizer, so it is kept off the
+    /// render pool.
+    pub async fn get(&self, af: &Client, url: Url, size: u32) -> Result<Bytes, PictureError> {
+        let key = format!("{size}:{url}");
+
+        if let Some(jpeg) = self.db.get_bytes(PICTURES, &key).await? {
+            return Ok(jpeg.into());
+        }
+
+        let original =
+            floorplan::download_limited(af.download(url).await?, MAX_DOWNLOAD_SIZE, |mime| {
+                mime.starts_with("image/")
+            })
+            .await?;
+        let jpeg = tokio::task::spawn_blocking(move || {
+            let mut img = image::load_from_memory(&original)?;
+
+            if img.width() > size || img.height() > size {
+                img = img.thumbnail(size, size);
+            }
+
+            let mut out = Cursor::new(Vec::new());
+            img.to_rgb8()
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY))?;
+            Ok::<_, image::ImageError>(out.into_inner())
+        })
+        .await
+        .unwrap()?;
+
+        self.db.insert_bytes(PICTURES, &key, jpeg.clone()).await?;
+
+        Ok(jpeg.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn size() {
+        assert_eq!(super::size(None), 1000);
+        assert_eq!(super::size(Some(1)), 250);
+        assert_eq!(super::size(Some(500)), 500);
+        assert_eq!(super::size(Some(501)), 1000);
+        assert_eq!(super::size(Some(10_000)), 2000);
+    }
+}
@@ -0,0 +1,87 @@
+//! Handlers of the routes that only exist under `/v1`, grouped by what they
+//! serve.
+
+use std::time::Duration;
+
+use axum::response::{IntoResponse, Response};
+use reqwest::{header, StatusCode};
+use tracing::error;
+
+use crate::{db::DbError, export::ExportError};
+
+pub mod calendar;
+pub mod export;
+pub mod feed;
+
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+pub struct AfError(#[from] pub afbostader::Error);
+
+impl AfError {
+    pub fn status_code(&self) -> StatusCode {
+        use afbostader::Error;
+
+        match self.0 {
+            Error::Http(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::BadCredentials => StatusCode::FORBIDDEN,
+            Error::Unauthenticated => StatusCode::UNAUTHORIZED,
+            Error::Unknown(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::UntrustedUrl(_) | Error::Layout(_) => StatusCode::BAD_GATEWAY,
+            Error::AreaNotFound(_) | Error::VacancyNotFound(_) => StatusCode::NOT_FOUND,
+            Error::UpstreamUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+impl IntoResponse for AfError {
+    fn into_response(self) -> Response {
+        (self.status_code(), self.to_string()).into_response()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HandlerError {
+    #[error("no such {0}")]
+    NotFound(&'static str),
+    #[error("too many requests, try again in {} seconds", .0.as_secs() + 1)]
+    RateLimited(Duration),
+    #[error("{0}")]
+    BadRequest(&'static str),
+    #[error(transparent)]
+    Af(#[from] AfError),
+    #[error(transparent)]
+    Db(#[from] DbError),
+    #[error(transparent)]
+    Export(#[from] ExportError),
+}
+
+impl From<afbostader::Error> for HandlerError {
+    fn from(value: afbostader::Error) -> Self {
+        Self::Af(value.into())
+    }
+}
+
+impl IntoResponse for HandlerError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::RateLimited(retry_after) => {
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, (retry_after.as_secs() + 1).to_string())],
+                    self.to_string(),
+                )
+                    .into_response()
+            }
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Af(ref e) => e.status_code(),
+            Self::Db(_) | Self::Export(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        if status.is_server_error() {
+            error!("Error: {:?}", self);
+        }
+
+        (status, self.to_string()).into_response()
+    }
+}
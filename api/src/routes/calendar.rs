@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_extra::TypedHeader;
+use headers::CacheControl;
+use reqwest::{header, StatusCode};
+use time::OffsetDateTime;
+
+use super::HandlerError;
+use crate::{
+    calendar,
+    filter::{VacancyFilter, MAX_AREA_LEN},
+    login::ClientIp,
+    v1, AppState,
+};
+
+async fn calendar_response(
+    state: &AppState,
+    filter: &VacancyFilter,
+) -> Result<Response, HandlerError> {
+    // calendar apps don't send cookies, so there are no queue positions
+    let vacancies: Vec<_> = state
+        .af
+        .list_vacancies()
+        .await?
+        .into_iter()
+        .filter(|p| filter.matches(p))
+        .collect();
+
+    Ok((
+        TypedHeader(
+            CacheControl::new()
+                .with_private()
+                .with_max_age(Duration::from_secs(3600)),
+        ),
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        calendar::render(&vacancies, &state.frontend_url, OffsetDateTime::now_utc()),
+    )
+        .into_response())
+}
+
+/// The reservation windows and move-in dates of vacancies, as an
+/// iCalendar feed.
+#[utoipa::path(
+    get,
+    path = "/v1/calendar.ics",
+    tag = "calendar",
+    params(VacancyFilter),
+    responses((status = 200, content_type = "text/calendar")),
+)]
+pub async fn get_calendar(
+    State(state): State<AppState>,
+    Query(filter): Query<VacancyFilter>,
+) -> Result<Response, HandlerError> {
+    calendar_response(&state, &filter).await
+}
+
+/// Save a filter, for a calendar URL that is short and doesn't reveal it.
+/// Calendars that aren't fetched for half a year are removed.
+#[utoipa::path(
+    post,
+    path = "/v1/calendars",
+    tag = "calendar",
+    request_body = VacancyFilter,
+    responses(
+        (status = 201, body = v1::CalendarLink),
+        (status = 400, description = "The area is longer than the name of any area"),
+        (status = 429, description = "Too many calendars saved from this address"),
+    ),
+)]
+pub async fn save_calendar(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    Json(filter): Json<VacancyFilter>,
+) -> Result<impl IntoResponse, HandlerError> {
+    if filter
+        .area
+        .as_ref()
+        .is_some_and(|area| area.chars().count() > MAX_AREA_LEN)
+    {
+        return Err(HandlerError::BadRequest("area is too long"));
+    }
+    state
+        .calendars
+        .attempt(ip)
+        .map_err(HandlerError::RateLimited)?;
+    let token = calendar::save(&state.db, &filter, OffsetDateTime::now_utc()).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(v1::CalendarLink {
+            url: format!("/v1/calendars/{token}"),
+        }),
+    ))
+}
+
+/// The calendar of a saved filter.
+#[utoipa::path(
+    get,
+    path = "/v1/calendars/{token}",
+    tag = "calendar",
+    params(("token" = String, Path)),
+    responses(
+        (status = 200, content_type = "text/calendar"),
+        (status = 404, description = "No such calendar"),
+    ),
+)]
+pub async fn get_saved_calendar(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Response, HandlerError> {
+    let filter = calendar::load(&state.db, &token, OffsetDateTime::now_utc())
+        .await?
+        .ok_or(HandlerError::NotFound("calendar"))?;
+
+    calendar_response(&state, &filter).await
+}
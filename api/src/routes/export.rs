@@ -0,0 +1,169 @@
+use std::net::IpAddr;
+
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+};
+use axum_extra::TypedHeader;
+use futures::{stream, StreamExt};
+use headers::CacheControl;
+use reqwest::{header, Url};
+use serde::Deserialize;
+use time::OffsetDateTime;
+use tracing::warn;
+use utoipa::IntoParams;
+
+use super::HandlerError;
+use crate::{
+    export::{self, ExportError, Row},
+    filter::VacancyFilter,
+    login::ClientIp,
+    vacancy, AppState, PersonalAf,
+};
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    /// Include what is only known from each vacancy's own page, which takes
+    /// a request to AF per vacancy, so each IP address can only make a few
+    /// such exports an hour.
+    /// Vacancies whose page fails to load get empty cells.
+    #[serde(default)]
+    detail: bool,
+}
+
+type ExportFormat = fn(&[Row], bool, &Url, time::Date) -> Result<Vec<u8>, ExportError>;
+
+async fn export_response(
+    state: &AppState,
+    af: &PersonalAf,
+    filter: &VacancyFilter,
+    query: &ExportQuery,
+    ip: IpAddr,
+    (content_type, filename): (&'static str, &'static str),
+    format: ExportFormat,
+) -> Result<Response, HandlerError> {
+    if query.detail {
+        state
+            .detailed_exports
+            .attempt(ip)
+            .map_err(HandlerError::RateLimited)?;
+    }
+    let vacancies: Vec<_> = af
+        .list_vacancies()
+        .await?
+        .into_iter()
+        .filter(|p| filter.matches(p))
+        .collect();
+    // a vacancy whose details fail to load gets empty detail cells rather
+    // than failing the export
+    let details = if query.detail {
+        // by ID, as a stream that borrows the properties makes the handler's
+        // future not provably `Send`
+        let ids: Vec<_> = vacancies.iter().map(|p| p.id).collect();
+        stream::iter(ids)
+            .map(|id| async move {
+                af.vacancy_detail(id)
+                    .await
+                    .inspect_err(|e| warn!("failed to load vacancy {id}: {e}"))
+                    .ok()
+            })
+            .buffered(vacancy::DETAIL_CONCURRENCY)
+            .collect()
+            .await
+    } else {
+        Vec::new()
+    };
+
+    let rows: Vec<_> = vacancies
+        .iter()
+        .enumerate()
+        .map(|(i, property)| Row {
+            property,
+            detail: details.get(i).and_then(Option::as_ref),
+        })
+        .collect();
+    let today = OffsetDateTime::now_utc().date();
+
+    Ok((
+        TypedHeader(CacheControl::new().with_private()),
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CONTENT_DISPOSITION, filename),
+        ],
+        format(&rows, query.detail, &state.frontend_url, today)?,
+    )
+        .into_response())
+}
+
+/// Vacancies as a CSV file, with rent per square metre and days left to
+/// register interest.
+#[utoipa::path(
+    get,
+    path = "/v1/vacancies.csv",
+    tag = "vacancies",
+    params(ExportQuery, VacancyFilter),
+    security((), ("login" = [])),
+    responses(
+        (status = 200, content_type = "text/csv"),
+        (status = 429, description = "Too many detailed exports from this address"),
+    ),
+)]
+pub async fn export_csv(
+    State(state): State<AppState>,
+    af: PersonalAf,
+    ClientIp(ip): ClientIp,
+    Query(query): Query<ExportQuery>,
+    Query(filter): Query<VacancyFilter>,
+) -> Result<Response, HandlerError> {
+    export_response(
+        &state,
+        &af,
+        &filter,
+        &query,
+        ip,
+        (
+            "text/csv; charset=utf-8",
+            "attachment; filename=\"lediga-bostader.csv\"",
+        ),
+        export::csv,
+    )
+    .await
+}
+
+/// Vacancies as an Excel workbook, like the CSV file.
+#[utoipa::path(
+    get,
+    path = "/v1/vacancies.xlsx",
+    tag = "vacancies",
+    params(ExportQuery, VacancyFilter),
+    security((), ("login" = [])),
+    responses(
+        (
+            status = 200,
+            content_type = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        ),
+        (status = 429, description = "Too many detailed exports from this address"),
+    ),
+)]
+pub async fn export_xlsx(
+    State(state): State<AppState>,
+    af: PersonalAf,
+    ClientIp(ip): ClientIp,
+    Query(query): Query<ExportQuery>,
+    Query(filter): Query<VacancyFilter>,
+) -> Result<Response, HandlerError> {
+    export_response(
+        &state,
+        &af,
+        &filter,
+        &query,
+        ip,
+        (
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            "attachment; filename=\"lediga-bostader.xlsx\"",
+        ),
+        export::xlsx,
+    )
+    .await
+}
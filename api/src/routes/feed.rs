@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+};
+use axum_extra::TypedHeader;
+use headers::CacheControl;
+use reqwest::{header, Url};
+use time::OffsetDateTime;
+
+use super::HandlerError;
+use crate::{
+    feed::{self, Entry},
+    filter::VacancyFilter,
+    AppState,
+};
+
+type FeedFormat = fn(&[Entry], &Url, OffsetDateTime) -> String;
+
+async fn feed_response(
+    state: &AppState,
+    filter: &VacancyFilter,
+    content_type: &'static str,
+    format: FeedFormat,
+) -> Result<Response, HandlerError> {
+    let now = OffsetDateTime::now_utc();
+    let vacancies: Vec<_> = state
+        .af
+        .list_vacancies()
+        .await?
+        .into_iter()
+        .filter(|p| filter.matches(p))
+        .collect();
+    let first_seen = feed::first_seen(&state.db, &vacancies, now).await?;
+
+    let mut entries: Vec<_> = vacancies
+        .iter()
+        .zip(first_seen)
+        .map(|(property, first_seen)| Entry {
+            property,
+            first_seen,
+        })
+        .collect();
+    feed::sort(&mut entries);
+
+    Ok((
+        TypedHeader(
+            CacheControl::new()
+                .with_public()
+                .with_max_age(Duration::from_secs(900)),
+        ),
+        [(header::CONTENT_TYPE, content_type)],
+        format(&entries, &state.frontend_url, now),
+    )
+        .into_response())
+}
+
+/// Vacancies as an Atom feed, newest first.
+#[utoipa::path(
+    get,
+    path = "/v1/feed.atom",
+    tag = "feeds",
+    params(VacancyFilter),
+    responses((status = 200, content_type = "application/atom+xml")),
+)]
+pub async fn get_atom_feed(
+    State(state): State<AppState>,
+    Query(filter): Query<VacancyFilter>,
+) -> Result<Response, HandlerError> {
+    feed_response(
+        &state,
+        &filter,
+        "application/atom+xml; charset=utf-8",
+        feed::atom,
+    )
+    .await
+}
+
+/// Vacancies as an RSS feed, newest first.
+#[utoipa::path(
+    get,
+    path = "/v1/feed.rss",
+    tag = "feeds",
+    params(VacancyFilter),
+    responses((status = 200, content_type = "application/rss+xml")),
+)]
+pub async fn get_rss_feed(
+    State(state): State<AppState>,
+    Query(filter): Query<VacancyFilter>,
+) -> Result<Response, HandlerError> {
+    feed_response(
+        &state,
+        &filter,
+        "application/rss+xml; charset=utf-8",
+        feed::rss,
+    )
+    .await
+}
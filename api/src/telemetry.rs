@@ -0,0 +1,146 @@
+//! Prometheus metrics, served at `/metrics`, and traces, exported over
+//! OTLP.
+
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use clap::ValueEnum;
+use metrics::{counter, histogram};
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle};
+use opentelemetry::trace::{TraceContextExt, TracerProvider};
+use opentelemetry_otlp::{ExporterBuildError, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+use reqwest::Url;
+use tracing::{field::Empty, info_span, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{
+    filter::LevelFilter, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer,
+};
+
+use crate::geocode::base_url;
+
+const SERVICE_NAME: &str = env!("CARGO_PKG_NAME");
+
+/// Histogram buckets for durations, from AF's fastest responses to
+/// floorplans that are about to time out.
+const DURATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Install the global metrics recorder. Metrics recorded before this are
+/// lost.
+pub fn install() -> Result<PrometheusHandle, BuildError> {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Suffix("_seconds".into()), DURATION_BUCKETS)?
+        .install_recorder()
+}
+
+/// Middleware that counts and times requests by route.
+pub async fn track_requests(req: Request, next: Next) -> Response {
+    // the route rather than the path, to keep the number of series bounded
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |p| p.as_str())
+        .to_owned();
+    let method = req.method().to_string();
+    let start = Instant::now();
+
+    let res = next.run(req).await;
+    let labels = [
+        ("route", route),
+        ("method", method),
+        ("status", res.status().as_u16().to_string()),
+    ];
+
+    histogram!("http_request_duration_seconds", &labels).record(start.elapsed());
+    counter!("http_requests_total", &labels).increment(1);
+
+    res
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line, including the trace ID of the request.
+    Json,
+}
+
+/// Set up logging, and export spans to the OTLP/HTTP collector at `otlp`
+/// if given. The returned provider must be kept alive for spans to be
+/// exported.
+pub fn init_tracing(
+    format: LogFormat,
+    otlp: Option<Url>,
+) -> Result<Option<SdkTracerProvider>, ExporterBuildError> {
+    let fmt = match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+    };
+    let provider = match otlp {
+        Some(url) => {
+            let exporter = SpanExporter::builder()
+                .with_http()
+                .with_endpoint(base_url(url).join("v1/traces").unwrap())
+                .build()?;
+
+            Some(
+                SdkTracerProvider::builder()
+                    .with_batch_exporter(exporter)
+                    .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+                    .build(),
+            )
+        }
+        None => None,
+    };
+    // `RUST_LOG` only applies to the logs, so that the spans of requests
+    // are exported without logging them
+    let otel = provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer(SERVICE_NAME))
+            .with_filter(LevelFilter::INFO)
+    });
+
+    // login attempts are always logged, unless `RUST_LOG` says otherwise
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("error,audit=info"));
+
+    tracing_subscriber::registry()
+        .with(fmt.with_filter(filter))
+        .with(otel)
+        .init();
+
+    Ok(provider)
+}
+
+/// The span of a request, named after its route.
+pub fn request_span(req: &Request) -> Span {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |p| p.as_str());
+    let span = info_span!(
+        "request",
+        otel.name = format!("{} {route}", req.method()),
+        method = %req.method(),
+        route,
+        status = Empty,
+        trace_id = Empty,
+    );
+
+    // so that the logs can be correlated with the exported trace
+    let trace_id = span.context().span().span_context().trace_id();
+    if trace_id != opentelemetry::trace::TraceId::INVALID {
+        span.record("trace_id", trace_id.to_string());
+    }
+
+    span
+}
@@ -0,0 +1,70 @@
+//! Fixtures shared by the tests of several modules.
+
+use std::{sync::Arc, time::Duration};
+
+use afbostader::{Product, ProductDetail, Property, PropertyDetail};
+use axum_extra::extract::cookie::{Key, SameSite};
+use reqwest::Url;
+
+use crate::{
+    commute::{Commutes, PointOfInterest, RouteProvider},
+    config::CookieSettings,
+    db::Db,
+    floorplan::RenderPool,
+    geocode::{Gazetteer, GeocodeCache},
+    graphql,
+    limit::IpLimiter,
+    login::{LoginLimiter, LoginLimits},
+    picture::Pictures,
+    AppState,
+};
+
+/// A vacancy as AF lists it.
+pub fn property() -> Property {
+    let product: Product =
+        serde_json::from_slice(include_bytes!("../../afbostader/src/product.json")).unwrap();
+
+    product.into()
+}
+
+/// The vacancy that AF's detail page was saved from.
+pub fn property_detail() -> PropertyDetail {
+    let detail: ProductDetail =
+        serde_json::from_slice(include_bytes!("../../afbostader/src/productDetail.json")).unwrap();
+
+    detail.into()
+}
+
+/// State with an in-memory database that knows no addresses, and routes
+/// to `pois` from `router`.
+pub fn state(pois: Vec<PointOfInterest>, router: Arc<dyn RouteProvider>) -> AppState {
+    let db = Db::in_memory().unwrap();
+    let gazetteer = Gazetteer::from_reader("street,postal_code,city,lat,lon\n".as_bytes()).unwrap();
+
+    AppState {
+        af: afbostader::Client::new(),
+        geocoder: GeocodeCache::new(Arc::new(gazetteer), db.clone()),
+        commutes: Commutes::new(pois, router),
+        key: Key::generate(),
+        cookies: CookieSettings {
+            secure: true,
+            same_site: SameSite::Lax,
+            domain: None,
+        },
+        pictures: Pictures::new(db.clone()),
+        db,
+        renderer: RenderPool::new(None, 1, 1, Duration::from_secs(1)),
+        af_ping: Default::default(),
+        login: LoginLimiter::new(LoginLimits {
+            per_ip: 10,
+            per_email: 10,
+            window: Duration::from_secs(60),
+        }),
+        calendars: IpLimiter::new(10, Duration::from_secs(60)),
+        ad_hoc_geocodes: IpLimiter::new(10, Duration::from_secs(60)),
+        detailed_exports: IpLimiter::new(10, Duration::from_secs(60)),
+        client_ip_header: None,
+        graphql: graphql::schema(),
+        frontend_url: Url::parse("https://bostader.example.com/").unwrap(),
+    }
+}
@@ -0,0 +1,399 @@
+//! The response types of `/v1`.
+//!
+//! These mirror the [`afbostader`] models, but are converted from them
+//! rather than being them, so that the library can change without changing
+//! the API. Fields are only ever added here.
+
+use async_graphql::SimpleObject;
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use serde::Serialize;
+use time::Date;
+use utoipa::ToSchema;
+
+use crate::{commute::Commute, vacancy};
+
+#[derive(Debug, Clone, Serialize, ToSchema, SimpleObject)]
+pub struct Address {
+    pub street: String,
+    pub city: String,
+    pub postal_code: String,
+}
+
+impl From<afbostader::Address> for Address {
+    fn from(a: afbostader::Address) -> Self {
+        Self {
+            street: a.street,
+            city: a.city,
+            postal_code: a.postal_code,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema, SimpleObject)]
+pub struct QueuePosition {
+    /// Our place in the queue, if logged in.
+    pub position: Option<u32>,
+    pub total_in_queue: u32,
+}
+
+impl From<afbostader::QueuePosition> for QueuePosition {
+    fn from(q: afbostader::QueuePosition) -> Self {
+        Self {
+            position: q.position,
+            total_in_queue: q.total_in_queue,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub enum PropertyType {
+    Apartment,
+    Dorm,
+    Other(String),
+}
+
+impl From<afbostader::PropertyType> for PropertyType {
+    fn from(t: afbostader::PropertyType) -> Self {
+        match t {
+            afbostader::PropertyType::Apartment => Self::Apartment,
+            afbostader::PropertyType::Dorm => Self::Dorm,
+            afbostader::PropertyType::Other(s) => Self::Other(s),
+        }
+    }
+}
+
+/// A vacant property, together with what we know about where it is.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Vacancy {
+    pub id: u32,
+    pub description: String,
+    pub short_description: String,
+    pub address: Address,
+    pub property_type: PropertyType,
+    pub area: String,
+    pub queue_position: QueuePosition,
+    pub reserved: bool,
+    pub floor: i8,
+    pub size_sqm: f32,
+    pub reserve_from: Date,
+    pub reserve_until: Date,
+    pub move_in: Date,
+    pub priority: Option<String>,
+    /// Monthly rent in SEK.
+    pub rent: u32,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    pub commutes: Vec<Commute>,
+}
+
+impl Vacancy {
+    fn new(
+        p: afbostader::Property,
+        lat: Option<f64>,
+        lon: Option<f64>,
+        commutes: Vec<Commute>,
+    ) -> Self {
+        Self {
+            id: p.id,
+            description: p.description,
+            short_description: p.short_description,
+            address: p.address.into(),
+            property_type: p.property_type.into(),
+            area: p.area,
+            queue_position: p.queue_position.into(),
+            reserved: p.reserved,
+            floor: p.floor,
+            size_sqm: p.size_sqm,
+            reserve_from: p.reserve_from,
+            reserve_until: p.reserve_until,
+            move_in: p.move_in,
+            priority: p.priority.map(|p| match p {
+                afbostader::Priority::Novisch => "Novisch".to_owned(),
+                afbostader::Priority::Other(s) => s,
+            }),
+            rent: p.rent,
+            lat,
+            lon,
+            commutes,
+        }
+    }
+}
+
+impl From<vacancy::Vacancy<afbostader::Property>> for Vacancy {
+    fn from(v: vacancy::Vacancy<afbostader::Property>) -> Self {
+        Self::new(v.inner, v.lat, v.lon, v.commutes)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema, SimpleObject)]
+pub struct Store {
+    pub included: String,
+    pub address: Option<String>,
+    pub size: Option<String>,
+    pub number: Option<u32>,
+}
+
+impl From<afbostader::Store> for Store {
+    fn from(s: afbostader::Store) -> Self {
+        Self {
+            included: s.included,
+            address: s.address,
+            size: s.size,
+            number: s.number,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema, SimpleObject)]
+pub struct Caretaker {
+    pub id: String,
+    pub email: String,
+    pub name: String,
+    pub phone: String,
+    pub work_phone: String,
+}
+
+impl From<afbostader::Worker> for Caretaker {
+    fn from(w: afbostader::Worker) -> Self {
+        Self {
+            id: w.id,
+            email: w.email,
+            name: w.name,
+            phone: w.phone,
+            work_phone: w.work_phone,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct VacancyDetail {
+    #[serde(flatten)]
+    pub vacancy: Vacancy,
+    pub status: String,
+    pub store: Store,
+    pub caretaker: Caretaker,
+    pub shower: String,
+    pub furniture: String,
+    pub balcony: String,
+    pub kitchen: String,
+    pub elevator: String,
+    pub heating: String,
+    pub electricity: String,
+    pub internet: String,
+    pub facing: String,
+    /// The floorplan on AF's website; use `/v1/vacancies/{id}/floorplan`
+    /// to get it as an image.
+    pub blueprint: Option<String>,
+}
+
+impl From<vacancy::Vacancy<afbostader::PropertyDetail>> for VacancyDetail {
+    fn from(v: vacancy::Vacancy<afbostader::PropertyDetail>) -> Self {
+        let d = v.inner;
+
+        Self {
+            vacancy: Vacancy::new(d.property, v.lat, v.lon, v.commutes),
+            status: d.status,
+            store: d.store.into(),
+            caretaker: d.caretaker.into(),
+            shower: d.shower,
+            furniture: d.furniture,
+            balcony: d.balcony,
+            kitchen: d.kitchen,
+            elevator: d.elevator,
+            heating: d.heating,
+            electricity: d.electricity,
+            internet: d.internet,
+            facing: d.facing,
+            blueprint: d.blueprint.map(String::from),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Area {
+    pub name: String,
+    pub slug: String,
+    /// The area's page on AF's website.
+    pub url: String,
+}
+
+impl From<afbostader::Area> for Area {
+    fn from(a: afbostader::Area) -> Self {
+        Self {
+            name: a.name,
+            slug: a.slug,
+            url: a.url.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AreaSummary {
+    #[serde(flatten)]
+    pub area: Area,
+    pub vacancies: usize,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema, SimpleObject)]
+pub struct Contact {
+    pub lines: Vec<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+}
+
+impl From<afbostader::Contact> for Contact {
+    fn from(c: afbostader::Contact) -> Self {
+        Self {
+            lines: c.lines,
+            email: c.email,
+            phone: c.phone,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Picture {
+    /// The original on AF's website; use
+    /// `/v1/areas/{name}/pictures/{idx}` to get a resized copy.
+    pub url: String,
+    pub alt: Option<String>,
+}
+
+impl From<afbostader::Picture> for Picture {
+    fn from(p: afbostader::Picture) -> Self {
+        Self {
+            url: p.url.into(),
+            alt: p.alt,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AreaDetail {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub facilities: Vec<String>,
+    /// Typical rents, as free-form text.
+    pub rents: Vec<String>,
+    pub addresses: Vec<String>,
+    pub contact: Option<Contact>,
+    pub pictures: Vec<Picture>,
+}
+
+impl From<afbostader::AreaDetail> for AreaDetail {
+    fn from(d: afbostader::AreaDetail) -> Self {
+        Self {
+            name: d.name,
+            description: d.description,
+            facilities: d.facilities,
+            rents: d.rents,
+            addresses: d.addresses,
+            contact: d.contact.map(Into::into),
+            pictures: d.pictures.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema, SimpleObject)]
+pub struct UserAddress {
+    pub street: String,
+    pub city: String,
+    pub postal_code: String,
+    pub county: Option<String>,
+    pub country: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema, SimpleObject)]
+pub struct User {
+    pub email: String,
+    pub personal_identity_number: Option<String>,
+    pub first_name: String,
+    pub last_name: String,
+    pub address: UserAddress,
+    pub mobile_phone: String,
+    pub start_year: Option<i32>,
+    pub start_semester: String,
+    pub date_of_birth: Option<Date>,
+}
+
+impl From<afbostader::User> for User {
+    fn from(u: afbostader::User) -> Self {
+        Self {
+            email: u.email,
+            personal_identity_number: u.personal_identity_number,
+            first_name: u.first_name,
+            last_name: u.last_name,
+            address: UserAddress {
+                street: u.address.street,
+                city: u.address.city,
+                postal_code: u.address.postal_code,
+                county: u.address.county,
+                country: u.address.country,
+            },
+            mobile_phone: u.mobile_phone,
+            start_year: u.start_year,
+            start_semester: u.start_semester,
+            date_of_birth: u.date_of_birth,
+        }
+    }
+}
+
+/// A saved calendar, to subscribe to.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CalendarLink {
+    /// Relative to the API.
+    pub url: String,
+}
+
+/// Mark a response from an unversioned route as deprecated, pointing at its
+/// `/v1` equivalent.
+pub async fn deprecated(req: Request, next: Next) -> Response {
+    let successor = format!("</v1{}>; rel=\"successor-version\"", req.uri().path());
+    let mut res = next.run(req).await;
+    let headers = res.headers_mut();
+
+    headers.insert(
+        HeaderName::from_static("deprecation"),
+        HeaderValue::from_static("true"),
+    );
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.append(axum::http::header::LINK, link);
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing, vacancy::Vacancy};
+
+    fn vacancy<T>(inner: T) -> Vacancy<T> {
+        Vacancy {
+            inner,
+            lat: Some(55.7),
+            lon: None,
+            commutes: Vec::new(),
+        }
+    }
+
+    /// `/v1` started out identical to the unversioned routes.
+    #[test]
+    fn same_as_library() {
+        let property = testing::property();
+        assert_eq!(
+            serde_json::to_value(vacancy(property.clone())).unwrap(),
+            serde_json::to_value(super::Vacancy::from(vacancy(property))).unwrap(),
+        );
+
+        let detail = testing::property_detail();
+        assert_eq!(
+            serde_json::to_value(vacancy(detail.clone())).unwrap(),
+            serde_json::to_value(super::VacancyDetail::from(vacancy(detail))).unwrap(),
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use afbostader::Property;
+use futures::{stream, StreamExt};
+use serde::Serialize;
+
+use crate::{commute::Commute, AppState};
+
+/// How many vacancies are located at once.
+const CONCURRENCY: usize = 16;
+
+/// How many vacancy pages are fetched from AF at once, where several are
+/// needed.
+pub const DETAIL_CONCURRENCY: usize = 4;
+
+/// A property from AF, together with what we know about where it is.
+#[derive(Debug, Serialize)]
+pub struct Vacancy<T> {
+    #[serde(flatten)]
+    pub inner: T,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    pub commutes: Vec<Commute>,
+}
+
+impl<T: AsRef<Property>> Vacancy<T> {
+    pub async fn new(inner: T, state: &AppState) -> Self {
+        let place = state.geocoder.locate(inner.as_ref()).await;
+        let commutes = match place {
+            Some(place) => state.commutes.from(place).await,
+            None => Vec::new(),
+        };
+
+        Self {
+            inner,
+            lat: place.map(|p| p.lat),
+            lon: place.map(|p| p.lon),
+            commutes,
+        }
+    }
+
+    /// Locate several vacancies, keeping their order.
+    pub async fn many(inner: impl IntoIterator<Item = T>, state: &AppState) -> Vec<Self> {
+        stream::iter(inner)
+            .map(|inner| Self::new(inner, state))
+            .buffered(CONCURRENCY)
+            .collect()
+            .await
+    }
+}
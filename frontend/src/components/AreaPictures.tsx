@@ -1,11 +1,21 @@
 "use client";
 
-import { Picture } from "@/lib/af";
+import { Picture, areaPictureUrl } from "@/lib/af";
 import { useArea } from "@/lib/hooks";
 import classNames from "classnames";
 import Image from "next/image";
 
-function AreaPicture({ picture, span }: { picture?: Picture; span: 1 | 2 }) {
+function AreaPicture({
+  area,
+  idx,
+  picture,
+  span,
+}: {
+  area?: string;
+  idx: number;
+  picture?: Picture;
+  span: 1 | 2;
+}) {
   const className = classNames(
     "bg-neutral-100 rounded-md sm:rounded-xl object-cover size-full aspect-square",
     {
@@ -15,12 +25,13 @@ function AreaPicture({ picture, span }: { picture?: Picture; span: 1 | 2 }) {
   );
   const size = 500 * span;
 
-  if (!picture) return <div className={className} />;
+  if (!area || !picture) return <div className={className} />;
 
   return (
     <Image
       className={className}
-      src={picture?.url}
+      src={areaPictureUrl(area, idx, size)}
+      unoptimized
       alt={picture?.alt || ""}
       width={size}
       height={size}
@@ -34,11 +45,15 @@ export default function AreaPictures(props: { area?: string }) {
 
   return (
     <div className="sm:gap-3p my-4 grid grid-cols-4 grid-rows-2 gap-2">
-      <AreaPicture picture={pictures?.[0]} span={2} />
-      <AreaPicture picture={pictures?.[1]} span={1} />
-      <AreaPicture picture={pictures?.[2]} span={1} />
-      <AreaPicture picture={pictures?.[3]} span={1} />
-      <AreaPicture picture={pictures?.[4]} span={1} />
+      {([2, 1, 1, 1, 1] as const).map((span, idx) => (
+        <AreaPicture
+          key={idx}
+          area={props.area}
+          idx={idx}
+          picture={pictures?.[idx]}
+          span={span}
+        />
+      ))}
     </div>
   );
 }
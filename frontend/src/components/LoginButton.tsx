@@ -22,9 +22,11 @@ export default function LoginButton({
   const [email, setEmail] = useState("");
   const [password, setPassword] = useState("");
   const [invalidCredentials, setInvalidCredentials] = useState(false);
+  const [rateLimited, setRateLimited] = useState(false);
 
   async function onSubmit(formData: FormData) {
     setInvalidCredentials(false);
+    setRateLimited(false);
 
     const res = await loginMutation.mutateAsync({
       email: formData.get("email")!.toString(),
@@ -32,6 +34,7 @@ export default function LoginButton({
     });
 
     setInvalidCredentials(res === "invalid-credentials");
+    setRateLimited(res === "rate-limited");
   }
 
   return (
@@ -82,6 +85,11 @@ export default function LoginButton({
               Felaktigt användarnamn eller lösenord.
             </p>
           )}
+          {rateLimited && (
+            <p className="rounded-lg border border-red-300 bg-red-50 p-4 text-red-500">
+              För många inloggningsförsök. Försök igen senare.
+            </p>
+          )}
           <button
             type="submit"
             disabled={loginMutation.isPending}
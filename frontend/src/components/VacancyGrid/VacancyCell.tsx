@@ -1,6 +1,6 @@
 "use client";
 
-import { Property, QueuePosition } from "@/lib/af";
+import { Property, QueuePosition, areaPictureUrl } from "@/lib/af";
 import { useArea, useVacancy } from "@/lib/hooks";
 import Link from "next/link";
 import { useEffect, useState } from "react";
@@ -124,7 +124,8 @@ export default function VacancyCell(props: { property: Property }) {
         <div className="relative mb-3 aspect-video w-full overflow-hidden rounded-2xl bg-neutral-100 shadow-sm">
           {picture && (
             <Image
-              src={picture.url}
+              src={areaPictureUrl(props.property.area, 0, 500)}
+              unoptimized
               alt={picture.alt || ""}
               width={500}
               height={500}
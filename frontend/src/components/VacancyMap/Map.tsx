@@ -1,46 +1,14 @@
 "use client";
 
-import { Address, API_URL, Property } from "@/lib/af";
+import { Property } from "@/lib/af";
 import { useVacancies } from "@/lib/hooks";
-import { useQuery } from "@tanstack/react-query";
 import L from "leaflet";
 import "leaflet/dist/leaflet.css";
 
 import { MapContainer, Marker, Popup, TileLayer } from "react-leaflet";
 
-interface Place {
-  lat: number;
-  lon: number;
-}
-
-function usePlace(address: Address) {
-  return useQuery<Place>({
-    queryKey: [
-      "geo",
-      {
-        street: address.street,
-        postalcode: address.postal_code,
-        city: address.city,
-      },
-    ],
-    queryFn: async () => {
-      const data = await fetch(
-        `${API_URL}/geocode?street=${encodeURIComponent(address.street)}&postalcode=${address.postal_code}&city=${address.city}`,
-        { cache: "force-cache" },
-      ).then((res) => res.json());
-      const place = data?.[0];
-      if (!place) throw new Error("no place");
-      return place;
-    },
-    refetchOnWindowFocus: false,
-    retry: true,
-  });
-}
-
 function VacancyMarker({ product }: { product: Property }) {
-  const { data } = usePlace(product.address);
-
-  if (!data) return null;
+  if (product.lat == null || product.lon == null) return null;
 
   const icon = L.divIcon({
     className:
@@ -49,7 +17,7 @@ function VacancyMarker({ product }: { product: Property }) {
   });
 
   return (
-    <Marker position={[data.lat, data.lon]} icon={icon}>
+    <Marker position={[product.lat, product.lon]} icon={icon}>
       <Popup>
         <strong>{product.area}:</strong> {product.description}
       </Popup>
@@ -1,4 +1,8 @@
-import { API_URL } from "@/lib/af";
+"use client";
+
+import classNames from "classnames";
+import { useEffect, useState } from "react";
+import { useFloorplan } from "@/lib/hooks";
 
 export default function VacancyFloorplan({
   id,
@@ -7,12 +11,45 @@ export default function VacancyFloorplan({
   id: number;
   className?: string;
 }) {
+  const { data, isError } = useFloorplan(id);
+  const [url, setUrl] = useState<string>();
+
+  useEffect(() => {
+    if (!data) return;
+
+    const url = URL.createObjectURL(data);
+    setUrl(url);
+
+    return () => {
+      URL.revokeObjectURL(url);
+      setUrl(undefined);
+    };
+  }, [data]);
+
+  if (isError)
+    return <p className={className}>Planritningen kunde inte hämtas.</p>;
+  if (data === null)
+    return <p className={className}>Bostaden har ingen planritning.</p>;
+  if (!data || !url) return null;
+
+  // the API serves the original PDF when it can't render it
+  if (data.type === "application/pdf") {
+    return (
+      <object
+        data={url}
+        type="application/pdf"
+        aria-label="Planritning över bostaden"
+        className={classNames("h-[80vh] w-full", className)}
+      >
+        <a href={url} download={`planritning-${id}.pdf`} className="underline">
+          Ladda ner planritningen (PDF)
+        </a>
+      </object>
+    );
+  }
+
   return (
     // eslint-disable-next-line @next/next/no-img-element
-    <img
-      src={`${API_URL}/vacancies/${id}/floorplan`}
-      alt="Planritning över bostaden"
-      className={className}
-    />
+    <img src={url} alt="Planritning över bostaden" className={className} />
   );
 }
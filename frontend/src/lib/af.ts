@@ -1,85 +1,90 @@
-export const API_URL = process.env.NEXT_PUBLIC_API_URL || "http://localhost:8000";
-
-export interface Address {
-  street: string;
-  city: string;
-  postal_code: string;
-}
-
-export interface QueuePosition {
-  position: number | null;
-  total_in_queue: number;
-}
-
-export type PropertyType = "Apartment" | "Dorm";
-
-export interface Property {
-  id: number;
-  property_type: PropertyType;
-  area: string;
-  description: string;
-  short_description: string;
-  address: Address;
-  floor: number;
-  size_sqm: number;
-  reserved: boolean;
-  queue_position: QueuePosition;
-  rent: number;
-  reserve_from: string;
-  reserve_until: string;
-  move_in: string;
-}
+import type { components } from "./api";
 
-export interface PropertyDetail extends Property {
-  facing: string;
-}
-
-export interface Area {}
-
-export interface Picture {
-  alt: string | null;
-  url: string;
-}
-
-export interface AreaDetail extends Area {
-  pictures: Picture[];
-}
+export const API_URL = process.env.NEXT_PUBLIC_API_URL || "http://localhost:8000";
+const V1 = `${API_URL}/v1`;
+
+type Schemas = components["schemas"];
+
+export type Address = Schemas["Address"];
+export type QueuePosition = Schemas["QueuePosition"];
+export type PropertyType = Schemas["PropertyType"];
+export type Property = Schemas["Vacancy"];
+export type PropertyDetail = Schemas["VacancyDetail"];
+export type Route = Schemas["Route"];
+export type Commute = Schemas["Commute"];
+export type Area = Schemas["Area"];
+export type AreaSummary = Schemas["AreaSummary"];
+export type Contact = Schemas["Contact"];
+export type Picture = Schemas["Picture"];
+export type AreaDetail = Schemas["AreaDetail"];
+export type EmailPassword = Schemas["EmailPassword"];
+export type UserDetails = Schemas["User"];
 
 export function listVacancies(): Promise<Property[]> {
-  return fetch(`${API_URL}/vacancies`, {
+  return fetch(`${V1}/vacancies`, {
     cache: "default",
     credentials: "include",
   }).then((res) => res.json());
 }
 
 export function getVacancy(id: number): Promise<PropertyDetail> {
-  return fetch(`${API_URL}/vacancies/${encodeURIComponent(id)}`, {
+  return fetch(`${V1}/vacancies/${encodeURIComponent(id)}`, {
+    cache: "default",
+    credentials: "include",
+  }).then((res) => res.json());
+}
+
+export function listAreas(): Promise<AreaSummary[]> {
+  return fetch(`${V1}/areas`, {
     cache: "default",
     credentials: "include",
   }).then((res) => res.json());
 }
 
 export function getArea(areaName: string): Promise<AreaDetail> {
-  return fetch(`${API_URL}/areas/${encodeURIComponent(areaName)}`, {
+  return fetch(`${V1}/areas/${encodeURIComponent(areaName)}`, {
     cache: "default",
     credentials: "include",
   }).then((res) => res.json());
 }
 
-export interface EmailPassword {
-  email: string;
-  password: string;
+/** The floorplan of a vacancy, as an image or a PDF. */
+export function floorplanUrl(id: number): string {
+  return `${V1}/vacancies/${encodeURIComponent(id)}/floorplan`;
 }
 
-export type LoginResponse = UserDetails | "invalid-credentials";
+/**
+ * The floorplan of a vacancy, or `null` if it has none. It is a PDF
+ * (`type` is `application/pdf`) when the API can't render it as an image.
+ */
+export async function getFloorplan(id: number): Promise<Blob | null> {
+  const res = await fetch(floorplanUrl(id), {
+    cache: "default",
+    credentials: "include",
+  });
+
+  if (res.status === 204) return null;
+  if (!res.ok) throw new Error(`failed to load floorplan: ${res.status}`);
+
+  return res.blob();
+}
 
-export interface UserDetails {
-  first_name: String;
-  last_name: String;
+/** A resized copy of an area picture, served by the API. */
+export function areaPictureUrl(
+  areaName: string,
+  idx: number,
+  size: number,
+): string {
+  return `${V1}/areas/${encodeURIComponent(areaName)}/pictures/${idx}?size=${size}`;
 }
 
+export type LoginResponse =
+  | UserDetails
+  | "invalid-credentials"
+  | "rate-limited";
+
 export async function getUser(): Promise<UserDetails | "unauthenticated"> {
-  const res = await fetch(`${API_URL}/user`, {
+  const res = await fetch(`${V1}/user`, {
     cache: "default",
     credentials: "include",
   });
@@ -90,7 +95,7 @@ export async function getUser(): Promise<UserDetails | "unauthenticated"> {
 }
 
 export async function login(details: EmailPassword): Promise<LoginResponse> {
-  const res = await fetch(`${API_URL}/login`, {
+  const res = await fetch(`${V1}/login`, {
     headers: {
       "content-type": "application/json",
     },
@@ -103,14 +108,16 @@ export async function login(details: EmailPassword): Promise<LoginResponse> {
     return res.json();
   } else if (res.status === 403) {
     return "invalid-credentials";
+  } else if (res.status === 429) {
+    return "rate-limited";
   } else {
     throw new Error(`unexpected response from server: ${await res.text()}`);
   }
 }
 
 export async function logout(): Promise<Response> {
-  return await fetch(`${API_URL}/logout`, {
-    cache: "default",
+  return await fetch(`${V1}/logout`, {
+    method: "POST",
     credentials: "include",
   });
 }
@@ -0,0 +1,1203 @@
+/**
+ * Generated from api/openapi.json; regenerate with `bun run generate:api`.
+ * Do not make direct changes to the file.
+ */
+
+export interface paths {
+    "/healthz": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        /** The process is up. */
+        get: operations["healthz"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/readyz": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        /** Everything needed to serve requests is available. */
+        get: operations["readyz"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/areas": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["list_areas"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/areas/{name}": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["get_area_detail"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/areas/{name}/pictures/{idx}": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        /** A picture of an area, resized and served from our own origin. */
+        get: operations["get_area_picture"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/calendar.ics": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        /**
+         * The reservation windows and move-in dates of vacancies, as an
+         * iCalendar feed.
+         */
+        get: operations["get_calendar"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/calendars": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get?: never;
+        put?: never;
+        /**
+         * Save a filter, for a calendar URL that is short and doesn't reveal it.
+         * Calendars that aren't fetched for half a year are removed.
+         */
+        post: operations["save_calendar"];
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/calendars/{token}": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        /** The calendar of a saved filter. */
+        get: operations["get_saved_calendar"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/feed.atom": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        /** Vacancies as an Atom feed, newest first. */
+        get: operations["get_atom_feed"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/feed.rss": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        /** Vacancies as an RSS feed, newest first. */
+        get: operations["get_rss_feed"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/geocode": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        /**
+         * Geocode an address. Addresses that AF lists are cached; others are
+         * limited per client and not stored.
+         * @description Unlike the unversioned `/geocode`, which still gives the coordinates as
+         *     strings like Nominatim, this is a list of numeric `{lat, lon}`.
+         */
+        get: operations["geocode"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/geocode/batch": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get?: never;
+        put?: never;
+        /**
+         * Geocode the addresses of vacancies. Addresses that AF doesn't list are
+         * not looked up, and come back as `null`.
+         */
+        post: operations["geocode_batch"];
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/login": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get?: never;
+        put?: never;
+        /** Check the credentials with AF and store them in an encrypted cookie. */
+        post: operations["login"];
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/logout": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get?: never;
+        put?: never;
+        post: operations["logout"];
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/user": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["user"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/vacancies": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["list_vacancies"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/vacancies.csv": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        /**
+         * Vacancies as a CSV file, with rent per square metre and days left to
+         * register interest.
+         */
+        get: operations["export_csv"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/vacancies.xlsx": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        /** Vacancies as an Excel workbook, like the CSV file. */
+        get: operations["export_xlsx"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/vacancies/{id}": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        get: operations["get_vacancy_detail"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/vacancies/{id}/floorplan": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        /** The floorplan of a vacancy. */
+        get: operations["get_vacancy_floorplan"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+    "/v1/vacancies/{id}/floorplan/rooms": {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        /** The rooms labelled on the floorplan of a vacancy. */
+        get: operations["get_vacancy_floorplan_rooms"];
+        put?: never;
+        post?: never;
+        delete?: never;
+        options?: never;
+        head?: never;
+        patch?: never;
+        trace?: never;
+    };
+}
+export type webhooks = Record<string, never>;
+export interface components {
+    schemas: {
+        Address: {
+            street: string;
+            city: string;
+            postal_code: string;
+        };
+        Area: {
+            name: string;
+            slug: string;
+            /** @description The area's page on AF's website. */
+            url: string;
+        };
+        AreaDetail: {
+            name?: string | null;
+            description?: string | null;
+            facilities: string[];
+            /** @description Typical rents, as free-form text. */
+            rents: string[];
+            addresses: string[];
+            contact?: null | components["schemas"]["Contact"];
+            pictures: components["schemas"]["Picture"][];
+        };
+        AreaSummary: components["schemas"]["Area"] & {
+            vacancies: number;
+        };
+        /** @description A saved calendar, to subscribe to. */
+        CalendarLink: {
+            /** @description Relative to the API. */
+            url: string;
+        };
+        Caretaker: {
+            id: string;
+            email: string;
+            name: string;
+            phone: string;
+            work_phone: string;
+        };
+        Check: {
+            latency_ms: number;
+            detail?: string | null;
+            status: "ok";
+        } | {
+            latency_ms: number;
+            detail: string;
+            status: "degraded";
+        } | {
+            latency_ms: number;
+            error: string;
+            status: "error";
+        };
+        /** @description The way from a vacancy to a [`PointOfInterest`]. */
+        Commute: {
+            to: string;
+            straight_line_m: number;
+            walking?: null | components["schemas"]["Route"];
+            cycling?: null | components["schemas"]["Route"];
+        };
+        Contact: {
+            lines: string[];
+            email?: string | null;
+            phone?: string | null;
+        };
+        EmailPassword: {
+            email: string;
+            password: string;
+        };
+        GeocodeQuery: {
+            street?: string | null;
+            city?: string | null;
+            postalcode?: string | null;
+        };
+        Picture: {
+            /**
+             * @description The original on AF's website; use
+             *     `/v1/areas/{name}/pictures/{idx}` to get a resized copy.
+             */
+            url: string;
+            alt?: string | null;
+        };
+        Place: {
+            lat: number;
+            lon: number;
+        };
+        /**
+         * @description The type of a property, without the name of other types, which are
+         *     too varied to filter by.
+         */
+        PropertyKind: "apartment" | "dorm" | "other";
+        PropertyType: "Apartment" | "Dorm" | {
+            Other: string;
+        };
+        QueuePosition: {
+            /** @description Our place in the queue, if logged in. */
+            position?: number | null;
+            total_in_queue: number;
+        };
+        Readiness: {
+            ready: boolean;
+            checks: {
+                [key: string]: components["schemas"]["Check"];
+            };
+        };
+        Room: {
+            name?: string | null;
+            area_sqm: number;
+        };
+        Route: {
+            distance_m: number;
+            duration_s: number;
+        };
+        Store: {
+            included: string;
+            address?: string | null;
+            size?: string | null;
+            number?: number | null;
+        };
+        User: {
+            email: string;
+            personal_identity_number?: string | null;
+            first_name: string;
+            last_name: string;
+            address: components["schemas"]["UserAddress"];
+            mobile_phone: string;
+            start_year?: number | null;
+            start_semester: string;
+            date_of_birth?: string | null;
+        };
+        UserAddress: {
+            street: string;
+            city: string;
+            postal_code: string;
+            county?: string | null;
+            country: string;
+        };
+        /** @description A vacant property, together with what we know about where it is. */
+        Vacancy: {
+            id: number;
+            description: string;
+            short_description: string;
+            address: components["schemas"]["Address"];
+            property_type: components["schemas"]["PropertyType"];
+            area: string;
+            queue_position: components["schemas"]["QueuePosition"];
+            reserved: boolean;
+            floor: number;
+            size_sqm: number;
+            reserve_from: string;
+            reserve_until: string;
+            move_in: string;
+            priority?: string | null;
+            /** @description Monthly rent in SEK. */
+            rent: number;
+            lat?: number | null;
+            lon?: number | null;
+            commutes: components["schemas"]["Commute"][];
+        };
+        VacancyDetail: components["schemas"]["Vacancy"] & {
+            status: string;
+            store: components["schemas"]["Store"];
+            caretaker: components["schemas"]["Caretaker"];
+            shower: string;
+            furniture: string;
+            balcony: string;
+            kitchen: string;
+            elevator: string;
+            heating: string;
+            electricity: string;
+            internet: string;
+            facing: string;
+            /**
+             * @description The floorplan on AF's website; use `/v1/vacancies/{id}/floorplan`
+             *     to get it as an image.
+             */
+            blueprint?: string | null;
+        };
+        /** @description Every condition that is set must hold. */
+        VacancyFilter: {
+            /** @description The name of the area, case insensitive. */
+            area?: string | null;
+            property_type?: null | components["schemas"]["PropertyKind"];
+            min_rent?: number | null;
+            max_rent?: number | null;
+            min_size_sqm?: number | null;
+            max_size_sqm?: number | null;
+            reserved?: boolean | null;
+        };
+    };
+    responses: never;
+    parameters: never;
+    requestBodies: never;
+    headers: never;
+    pathItems: never;
+}
+export type $defs = Record<string, never>;
+export interface operations {
+    healthz: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    readyz: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["Readiness"];
+                };
+            };
+            503: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["Readiness"];
+                };
+            };
+        };
+    };
+    list_areas: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["AreaSummary"][];
+                };
+            };
+        };
+    };
+    get_area_detail: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path: {
+                name: string;
+            };
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["AreaDetail"];
+                };
+            };
+            /** @description No such area */
+            404: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    get_area_picture: {
+        parameters: {
+            query?: {
+                /** @description The largest width or height wanted, in pixels. */
+                size?: number;
+            };
+            header?: never;
+            path: {
+                name: string;
+                idx: number;
+            };
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "image/jpeg": unknown;
+                };
+            };
+            /** @description No such area or picture */
+            404: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    get_calendar: {
+        parameters: {
+            query?: {
+                /** @description The name of the area, case insensitive. */
+                area?: string;
+                property_type?: components["schemas"]["PropertyKind"];
+                min_rent?: number;
+                max_rent?: number;
+                min_size_sqm?: number;
+                max_size_sqm?: number;
+                reserved?: boolean;
+            };
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "text/calendar": unknown;
+                };
+            };
+        };
+    };
+    save_calendar: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody: {
+            content: {
+                "application/json": components["schemas"]["VacancyFilter"];
+            };
+        };
+        responses: {
+            201: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["CalendarLink"];
+                };
+            };
+            /** @description The area is longer than the name of any area */
+            400: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+            /** @description Too many calendars saved from this address */
+            429: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    get_saved_calendar: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path: {
+                token: string;
+            };
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "text/calendar": unknown;
+                };
+            };
+            /** @description No such calendar */
+            404: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    get_atom_feed: {
+        parameters: {
+            query?: {
+                /** @description The name of the area, case insensitive. */
+                area?: string;
+                property_type?: components["schemas"]["PropertyKind"];
+                min_rent?: number;
+                max_rent?: number;
+                min_size_sqm?: number;
+                max_size_sqm?: number;
+                reserved?: boolean;
+            };
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/atom+xml": unknown;
+                };
+            };
+        };
+    };
+    get_rss_feed: {
+        parameters: {
+            query?: {
+                /** @description The name of the area, case insensitive. */
+                area?: string;
+                property_type?: components["schemas"]["PropertyKind"];
+                min_rent?: number;
+                max_rent?: number;
+                min_size_sqm?: number;
+                max_size_sqm?: number;
+                reserved?: boolean;
+            };
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/rss+xml": unknown;
+                };
+            };
+        };
+    };
+    geocode: {
+        parameters: {
+            query?: {
+                street?: string;
+                city?: string;
+                postalcode?: string;
+            };
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description At most one place, as a list */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["Place"][];
+                };
+            };
+            /** @description Too many addresses that AF doesn't list */
+            429: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+            /** @description Too many addresses are being geocoded */
+            503: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    geocode_batch: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody: {
+            content: {
+                "application/json": components["schemas"]["GeocodeQuery"][];
+            };
+        };
+        responses: {
+            /** @description The place of each address, in order */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": null | components["schemas"]["Place"][];
+                };
+            };
+            /** @description Too many addresses */
+            413: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+            /** @description Too many addresses are being geocoded */
+            503: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    login: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody: {
+            content: {
+                "application/json": components["schemas"]["EmailPassword"];
+            };
+        };
+        responses: {
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["User"];
+                };
+            };
+            /** @description Bad credentials */
+            403: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+            /** @description Too many attempts */
+            429: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    logout: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    user: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["User"];
+                };
+            };
+            /** @description Not logged in */
+            401: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    list_vacancies: {
+        parameters: {
+            query?: {
+                /** @description Sort by the distance to this point of interest. */
+                sort_by?: string;
+                /** @description Sort by travel time rather than straight-line distance. */
+                mode?: components["schemas"]["Mode"];
+                /** @description The name of the area, case insensitive. */
+                area?: string;
+                property_type?: components["schemas"]["PropertyKind"];
+                min_rent?: number;
+                max_rent?: number;
+                min_size_sqm?: number;
+                max_size_sqm?: number;
+                reserved?: boolean;
+            };
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["Vacancy"][];
+                };
+            };
+            /** @description Unknown point of interest */
+            400: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    export_csv: {
+        parameters: {
+            query?: {
+                /**
+                 * @description Include what is only known from each vacancy's own page, which takes
+                 *     a request to AF per vacancy, so each IP address can only make a few
+                 *     such exports an hour.
+                 *     Vacancies whose page fails to load get empty cells.
+                 */
+                detail?: boolean;
+                /** @description The name of the area, case insensitive. */
+                area?: string;
+                property_type?: components["schemas"]["PropertyKind"];
+                min_rent?: number;
+                max_rent?: number;
+                min_size_sqm?: number;
+                max_size_sqm?: number;
+                reserved?: boolean;
+            };
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "text/csv": unknown;
+                };
+            };
+            /** @description Too many detailed exports from this address */
+            429: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    export_xlsx: {
+        parameters: {
+            query?: {
+                /**
+                 * @description Include what is only known from each vacancy's own page, which takes
+                 *     a request to AF per vacancy, so each IP address can only make a few
+                 *     such exports an hour.
+                 *     Vacancies whose page fails to load get empty cells.
+                 */
+                detail?: boolean;
+                /** @description The name of the area, case insensitive. */
+                area?: string;
+                property_type?: components["schemas"]["PropertyKind"];
+                min_rent?: number;
+                max_rent?: number;
+                min_size_sqm?: number;
+                max_size_sqm?: number;
+                reserved?: boolean;
+            };
+            header?: never;
+            path?: never;
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet": unknown;
+                };
+            };
+            /** @description Too many detailed exports from this address */
+            429: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    get_vacancy_detail: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path: {
+                id: number;
+            };
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["VacancyDetail"];
+                };
+            };
+        };
+    };
+    get_vacancy_floorplan: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path: {
+                id: number;
+            };
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            /** @description A PNG, or a PDF if it cannot be rendered */
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "image/png": unknown;
+                    "application/pdf": unknown;
+                };
+            };
+            /** @description The vacancy has no floorplan */
+            204: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+    get_vacancy_floorplan_rooms: {
+        parameters: {
+            query?: never;
+            header?: never;
+            path: {
+                id: number;
+            };
+            cookie?: never;
+        };
+        requestBody?: never;
+        responses: {
+            200: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content: {
+                    "application/json": components["schemas"]["Room"][];
+                };
+            };
+            /** @description The vacancy has no floorplan */
+            204: {
+                headers: {
+                    [name: string]: unknown;
+                };
+                content?: never;
+            };
+        };
+    };
+}
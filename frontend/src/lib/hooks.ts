@@ -3,6 +3,7 @@
 import { useQuery, useQueryClient } from "@tanstack/react-query";
 import {
   getArea,
+  getFloorplan,
   getUser,
   getVacancy,
   listVacancies,
@@ -35,6 +36,14 @@ export function useVacancy(id: number, initialData?: PropertyDetail) {
   });
 }
 
+export function useFloorplan(id: number) {
+  return useQuery({
+    queryKey: ["vacancies", id, "floorplan"],
+    queryFn: () => getFloorplan(id),
+    staleTime: Infinity,
+  });
+}
+
 export function useAreaNames() {
   const { data: vacancies } = useVacancies();
 